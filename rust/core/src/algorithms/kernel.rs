@@ -0,0 +1,581 @@
+use glam::*;
+
+use crate::Falloff;
+
+// Neighbours are gathered into batches of this many lanes before being handed to the kernel
+const LANES: usize = 8;
+
+/// Instruction set used to accumulate separation, alignment and cohesion over neighbours.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum NeighborKernel {
+    /// Plain scalar code, available everywhere.
+    #[default]
+    Scalar,
+    /// 4 neighbours per instruction, baseline on x86_64.
+    Sse,
+    /// 8 neighbours per instruction on x86_64 CPUs supporting AVX.
+    Avx,
+    /// 4 neighbours per instruction, baseline on aarch64.
+    Neon,
+}
+
+impl NeighborKernel {
+    /// Picks the widest kernel the running CPU supports.
+    pub fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if std::arch::is_x86_feature_detected!("avx") {
+                return Self::Avx;
+            }
+            Self::Sse
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            Self::Neon
+        }
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+        {
+            Self::Scalar
+        }
+    }
+    
+    /// Whether the running CPU can execute this kernel.
+    pub fn is_supported(self) -> bool {
+        match self {
+            Self::Scalar => true,
+            #[cfg(target_arch = "x86_64")]
+            Self::Sse => true,
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx => std::arch::is_x86_feature_detected!("avx"),
+            #[cfg(target_arch = "aarch64")]
+            Self::Neon => true,
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+}
+
+// Squared perception radii of a flock
+#[derive(Clone, Copy)]
+pub(crate) struct NeighborRadii {
+    pub sep_dist_sq: f32,
+    pub align_dist_sq: f32,
+    pub cohere_dist_sq: f32,
+}
+
+// Perception cones of a moving boid, neighbours behind it are only seen by rules with a wide enough cone
+#[derive(Clone, Copy)]
+pub(crate) struct NeighborView {
+    pub heading: Vec3,
+    // Cosine of half the view angle, for separation, alignment and cohesion
+    pub cos_half: [f32; 3],
+}
+
+// Distance falloff of separation, alignment and cohesion
+#[derive(Clone, Copy)]
+pub(crate) struct NeighborFalloff {
+    pub rules: [Falloff; 3],
+    // Inverse of the (not squared) radius of each rule
+    pub inv_radius: [f32; 3],
+}
+
+impl NeighborFalloff {
+    // What the offset away from a neighbour gets scaled by, then the weight of the neighbour for each rule.
+    // Velocity and position of a neighbour get scaled by the weights of alignment and cohesion
+    #[inline(always)]
+    pub fn lane_weights(&self, dist_sq: f32, dist: f32) -> [f32; 4] {
+        [
+            separation_scale(&self.rules[0], dist_sq, dist, self.inv_radius[0]),
+            self.rules[0].weight(dist * self.inv_radius[0]),
+            self.rules[1].weight(dist * self.inv_radius[1]),
+            self.rules[2].weight(dist * self.inv_radius[2]),
+        ]
+    }
+    
+    // `lane_weights` for a whole register, the SIMD kernels store their distances and load the weights back
+    #[inline(always)]
+    fn weights<const N: usize>(&self, dist_sq: &[f32; N], dist: &[f32; N]) -> [[f32; N]; 4] {
+        let mut weights = [[0.0; N]; 4];
+        for lane in 0..N {
+            let lane_weights = self.lane_weights(dist_sq[lane], dist[lane]);
+            for (weights, weight) in weights.iter_mut().zip(lane_weights) {
+                weights[lane] = weight;
+            }
+        }
+        weights
+    }
+}
+
+// Factor the offset away from a neighbour is scaled by for separation, no falloff keeps the classic inverse square push
+#[inline(always)]
+pub(crate) fn separation_scale(falloff: &Falloff, dist_sq: f32, dist: f32, inv_radius: f32) -> f32 {
+    match falloff {
+        Falloff::None => 1.0 / (dist_sq * dist),
+        falloff => falloff.weight(dist * inv_radius) / dist,
+    }
+}
+
+// Result of accumulating a boid's neighbours
+#[derive(Clone, Copy, Default)]
+pub(crate) struct NeighborSums {
+    pub sep_sum: Vec3,
+    pub align_sum: Vec3,
+    pub cohere_sum: Vec3,
+    pub counts: [u32; 3],
+    // Total falloff weight of the neighbours of each rule, their count without falloff
+    pub weights: [f32; 3],
+}
+
+// Per-lane partial sums, only reduced once all neighbours were visited
+#[derive(Default)]
+struct LaneSums {
+    sep: [[f32; LANES]; 3],
+    align: [[f32; LANES]; 3],
+    cohere: [[f32; LANES]; 3],
+    weights: [[f32; LANES]; 3],
+    counts: [u32; 3],
+}
+
+// Gathers neighbours from the SoA buffers and accumulates them `LANES` at a time
+pub(crate) struct NeighborBatch {
+    kernel: NeighborKernel,
+    position: Vec3,
+    radii: NeighborRadii,
+    view: Option<NeighborView>,
+    falloff: Option<NeighborFalloff>,
+    len: usize,
+    pos: [[f32; LANES]; 3],
+    vel: [[f32; LANES]; 3],
+    sums: LaneSums,
+}
+
+impl NeighborBatch {
+    /// `kernel` must be supported by the running CPU, see [`NeighborKernel::is_supported`].
+    /// Without a `view`, neighbours are seen all around. Without a `falloff`, no rule has one.
+    #[inline(always)]
+    pub fn new(
+        kernel: NeighborKernel,
+        position: Vec3,
+        radii: NeighborRadii,
+        view: Option<NeighborView>,
+        falloff: Option<NeighborFalloff>,
+    ) -> Self {
+        Self {
+            kernel,
+            position,
+            radii,
+            view,
+            falloff,
+            len: 0,
+            pos: [[0.0; LANES]; 3],
+            vel: [[0.0; LANES]; 3],
+            sums: LaneSums::default(),
+        }
+    }
+    
+    #[inline(always)]
+    pub fn push(&mut self, position: Vec3, velocity: Vec3) {
+        let lane = self.len;
+        self.pos[0][lane] = position.x;
+        self.pos[1][lane] = position.y;
+        self.pos[2][lane] = position.z;
+        self.vel[0][lane] = velocity.x;
+        self.vel[1][lane] = velocity.y;
+        self.vel[2][lane] = velocity.z;
+        self.len += 1;
+        if self.len == LANES {
+            self.flush();
+        }
+    }
+    
+    pub fn finish(mut self) -> NeighborSums {
+        if self.len > 0 {
+            // Pad with the boid's own position, which the kernels skip like any overlapping neighbour
+            for lane in self.len..LANES {
+                self.pos[0][lane] = self.position.x;
+                self.pos[1][lane] = self.position.y;
+                self.pos[2][lane] = self.position.z;
+            }
+            self.flush();
+        }
+        
+        let reduce = |lanes: &[[f32; LANES]; 3]| {
+            Vec3::new(lanes[0].iter().sum(), lanes[1].iter().sum(), lanes[2].iter().sum())
+        };
+        NeighborSums {
+            sep_sum: reduce(&self.sums.sep),
+            align_sum: reduce(&self.sums.align),
+            cohere_sum: reduce(&self.sums.cohere),
+            counts: self.sums.counts,
+            weights: match self.falloff {
+                Some(_) => self.sums.weights.map(|lanes| lanes.iter().sum()),
+                None => self.sums.counts.map(|count| count as f32),
+            },
+        }
+    }
+    
+    #[inline(always)]
+    fn flush(&mut self) {
+        self.len = 0;
+        match self.kernel {
+            // SAFETY: `new` requires the kernel to be supported by the running CPU
+            #[cfg(target_arch = "x86_64")]
+            NeighborKernel::Avx => unsafe { self.flush_avx() },
+            #[cfg(target_arch = "x86_64")]
+            NeighborKernel::Sse => unsafe { self.flush_sse() },
+            #[cfg(target_arch = "aarch64")]
+            NeighborKernel::Neon => unsafe { self.flush_neon() },
+            _ => self.flush_scalar(),
+        }
+    }
+    
+    fn flush_scalar(&mut self) {
+        let radii = self.radii;
+        for lane in 0..LANES {
+            let other_pos = Vec3::new(self.pos[0][lane], self.pos[1][lane], self.pos[2][lane]);
+            let diff = self.position - other_pos;
+            let dist_sq = diff.length_squared();
+            if dist_sq < f32::EPSILON { continue; }
+            
+            let dist = dist_sq.sqrt();
+            
+            // `diff` points away from the neighbour, so it's ahead when `diff` points backwards
+            let in_view = match self.view {
+                Some(view) => {
+                    let ahead = -view.heading.dot(diff);
+                    view.cos_half.map(|cos_half| ahead >= cos_half * dist)
+                }
+                None => [true; 3],
+            };
+            let [sep_scale, sep_weight, align_scale, cohere_scale] = match &self.falloff {
+                Some(falloff) => falloff.lane_weights(dist_sq, dist),
+                None => [1.0 / (dist_sq * dist), 1.0, 1.0, 1.0],
+            };
+            
+            if in_view[0] && dist_sq < radii.sep_dist_sq {
+                for axis in 0..3 {
+                    self.sums.sep[axis][lane] += diff[axis] * sep_scale;
+                }
+                self.sums.weights[0][lane] += sep_weight;
+                self.sums.counts[0] += 1;
+            }
+            if in_view[1] && dist_sq < radii.align_dist_sq {
+                for axis in 0..3 {
+                    self.sums.align[axis][lane] += self.vel[axis][lane] * align_scale;
+                }
+                self.sums.weights[1][lane] += align_scale;
+                self.sums.counts[1] += 1;
+            }
+            if in_view[2] && dist_sq < radii.cohere_dist_sq {
+                for axis in 0..3 {
+                    self.sums.cohere[axis][lane] += self.pos[axis][lane] * cohere_scale;
+                }
+                self.sums.weights[2][lane] += cohere_scale;
+                self.sums.counts[2] += 1;
+            }
+        }
+    }
+    
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx")]
+    unsafe fn flush_avx(&mut self) {
+        use std::arch::x86_64::*;
+        
+        let dx = _mm256_sub_ps(_mm256_set1_ps(self.position.x), _mm256_loadu_ps(self.pos[0].as_ptr()));
+        let dy = _mm256_sub_ps(_mm256_set1_ps(self.position.y), _mm256_loadu_ps(self.pos[1].as_ptr()));
+        let dz = _mm256_sub_ps(_mm256_set1_ps(self.position.z), _mm256_loadu_ps(self.pos[2].as_ptr()));
+        let dist_sq = _mm256_add_ps(_mm256_mul_ps(dx, dx), _mm256_add_ps(_mm256_mul_ps(dy, dy), _mm256_mul_ps(dz, dz)));
+        
+        let valid = _mm256_cmp_ps::<_CMP_GE_OQ>(dist_sq, _mm256_set1_ps(f32::EPSILON));
+        let sep = _mm256_and_ps(valid, _mm256_cmp_ps::<_CMP_LT_OQ>(dist_sq, _mm256_set1_ps(self.radii.sep_dist_sq)));
+        let align = _mm256_and_ps(valid, _mm256_cmp_ps::<_CMP_LT_OQ>(dist_sq, _mm256_set1_ps(self.radii.align_dist_sq)));
+        let cohere = _mm256_and_ps(valid, _mm256_cmp_ps::<_CMP_LT_OQ>(dist_sq, _mm256_set1_ps(self.radii.cohere_dist_sq)));
+        
+        // `diff` points away from the neighbour, so it's ahead when `diff` points backwards
+        let (sep, align, cohere) = match self.view {
+            Some(view) => {
+                let heading_dot = _mm256_add_ps(
+                    _mm256_mul_ps(_mm256_set1_ps(view.heading.x), dx),
+                    _mm256_add_ps(_mm256_mul_ps(_mm256_set1_ps(view.heading.y), dy), _mm256_mul_ps(_mm256_set1_ps(view.heading.z), dz)),
+                );
+                let ahead = _mm256_sub_ps(_mm256_setzero_ps(), heading_dot);
+                let dist = _mm256_sqrt_ps(dist_sq);
+                let [sep_cos, align_cos, cohere_cos] = view.cos_half.map(|cos_half| _mm256_set1_ps(cos_half));
+                (
+                    _mm256_and_ps(sep, _mm256_cmp_ps::<_CMP_GE_OQ>(ahead, _mm256_mul_ps(sep_cos, dist))),
+                    _mm256_and_ps(align, _mm256_cmp_ps::<_CMP_GE_OQ>(ahead, _mm256_mul_ps(align_cos, dist))),
+                    _mm256_and_ps(cohere, _mm256_cmp_ps::<_CMP_GE_OQ>(ahead, _mm256_mul_ps(cohere_cos, dist))),
+                )
+            }
+            None => (sep, align, cohere),
+        };
+        
+        // Masked out lanes may divide by zero, the mask clears the resulting NaNs
+        let (sep_scale, align_scale, cohere_scale) = match &self.falloff {
+            Some(falloff) => {
+                let (mut lane_dist_sq, mut lane_dist) = ([0.0; LANES], [0.0; LANES]);
+                _mm256_storeu_ps(lane_dist_sq.as_mut_ptr(), dist_sq);
+                _mm256_storeu_ps(lane_dist.as_mut_ptr(), _mm256_sqrt_ps(dist_sq));
+                let [sep_s, sep_w, align_w, cohere_w] = falloff.weights(&lane_dist_sq, &lane_dist);
+                let [sep_w, align_w, cohere_w] = [sep_w, align_w, cohere_w].map(|w| _mm256_loadu_ps(w.as_ptr()));
+                for (rule, (mask, w)) in [(sep, sep_w), (align, align_w), (cohere, cohere_w)].into_iter().enumerate() {
+                    let acc = self.sums.weights[rule].as_mut_ptr();
+                    _mm256_storeu_ps(acc, _mm256_add_ps(_mm256_loadu_ps(acc), _mm256_and_ps(mask, w)));
+                }
+                (_mm256_loadu_ps(sep_s.as_ptr()), Some(align_w), Some(cohere_w))
+            }
+            None => (_mm256_div_ps(_mm256_set1_ps(1.0), _mm256_mul_ps(dist_sq, _mm256_sqrt_ps(dist_sq))), None, None),
+        };
+        let sums = &mut self.sums;
+        for (axis, diff) in [dx, dy, dz].into_iter().enumerate() {
+            let acc = sums.sep[axis].as_mut_ptr();
+            _mm256_storeu_ps(acc, _mm256_add_ps(_mm256_loadu_ps(acc), _mm256_and_ps(sep, _mm256_mul_ps(diff, sep_scale))));
+            let acc = sums.align[axis].as_mut_ptr();
+            let mut vel = _mm256_loadu_ps(self.vel[axis].as_ptr());
+            if let Some(scale) = align_scale {
+                vel = _mm256_mul_ps(vel, scale);
+            }
+            _mm256_storeu_ps(acc, _mm256_add_ps(_mm256_loadu_ps(acc), _mm256_and_ps(align, vel)));
+            let acc = sums.cohere[axis].as_mut_ptr();
+            let mut pos = _mm256_loadu_ps(self.pos[axis].as_ptr());
+            if let Some(scale) = cohere_scale {
+                pos = _mm256_mul_ps(pos, scale);
+            }
+            _mm256_storeu_ps(acc, _mm256_add_ps(_mm256_loadu_ps(acc), _mm256_and_ps(cohere, pos)));
+        }
+        
+        sums.counts[0] += _mm256_movemask_ps(sep).count_ones();
+        sums.counts[1] += _mm256_movemask_ps(align).count_ones();
+        sums.counts[2] += _mm256_movemask_ps(cohere).count_ones();
+    }
+    
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "sse2")]
+    unsafe fn flush_sse(&mut self) {
+        use std::arch::x86_64::*;
+        
+        for half in [0, 4] {
+            let dx = _mm_sub_ps(_mm_set1_ps(self.position.x), _mm_loadu_ps(self.pos[0][half..].as_ptr()));
+            let dy = _mm_sub_ps(_mm_set1_ps(self.position.y), _mm_loadu_ps(self.pos[1][half..].as_ptr()));
+            let dz = _mm_sub_ps(_mm_set1_ps(self.position.z), _mm_loadu_ps(self.pos[2][half..].as_ptr()));
+            let dist_sq = _mm_add_ps(_mm_mul_ps(dx, dx), _mm_add_ps(_mm_mul_ps(dy, dy), _mm_mul_ps(dz, dz)));
+            
+            let valid = _mm_cmpge_ps(dist_sq, _mm_set1_ps(f32::EPSILON));
+            let sep = _mm_and_ps(valid, _mm_cmplt_ps(dist_sq, _mm_set1_ps(self.radii.sep_dist_sq)));
+            let align = _mm_and_ps(valid, _mm_cmplt_ps(dist_sq, _mm_set1_ps(self.radii.align_dist_sq)));
+            let cohere = _mm_and_ps(valid, _mm_cmplt_ps(dist_sq, _mm_set1_ps(self.radii.cohere_dist_sq)));
+            
+            // `diff` points away from the neighbour, so it's ahead when `diff` points backwards
+            let (sep, align, cohere) = match self.view {
+                Some(view) => {
+                    let heading_dot = _mm_add_ps(
+                        _mm_mul_ps(_mm_set1_ps(view.heading.x), dx),
+                        _mm_add_ps(_mm_mul_ps(_mm_set1_ps(view.heading.y), dy), _mm_mul_ps(_mm_set1_ps(view.heading.z), dz)),
+                    );
+                    let ahead = _mm_sub_ps(_mm_setzero_ps(), heading_dot);
+                    let dist = _mm_sqrt_ps(dist_sq);
+                    let [sep_cos, align_cos, cohere_cos] = view.cos_half.map(|cos_half| _mm_set1_ps(cos_half));
+                    (
+                        _mm_and_ps(sep, _mm_cmpge_ps(ahead, _mm_mul_ps(sep_cos, dist))),
+                        _mm_and_ps(align, _mm_cmpge_ps(ahead, _mm_mul_ps(align_cos, dist))),
+                        _mm_and_ps(cohere, _mm_cmpge_ps(ahead, _mm_mul_ps(cohere_cos, dist))),
+                    )
+                }
+                None => (sep, align, cohere),
+            };
+            
+            // Masked out lanes may divide by zero, the mask clears the resulting NaNs
+            let (sep_scale, align_scale, cohere_scale) = match &self.falloff {
+                Some(falloff) => {
+                    let (mut lane_dist_sq, mut lane_dist) = ([0.0; 4], [0.0; 4]);
+                    _mm_storeu_ps(lane_dist_sq.as_mut_ptr(), dist_sq);
+                    _mm_storeu_ps(lane_dist.as_mut_ptr(), _mm_sqrt_ps(dist_sq));
+                    let [sep_s, sep_w, align_w, cohere_w] = falloff.weights(&lane_dist_sq, &lane_dist);
+                    let [sep_w, align_w, cohere_w] = [sep_w, align_w, cohere_w].map(|w| _mm_loadu_ps(w.as_ptr()));
+                    for (rule, (mask, w)) in [(sep, sep_w), (align, align_w), (cohere, cohere_w)].into_iter().enumerate() {
+                        let acc = self.sums.weights[rule][half..].as_mut_ptr();
+                        _mm_storeu_ps(acc, _mm_add_ps(_mm_loadu_ps(acc), _mm_and_ps(mask, w)));
+                    }
+                    (_mm_loadu_ps(sep_s.as_ptr()), Some(align_w), Some(cohere_w))
+                }
+                None => (_mm_div_ps(_mm_set1_ps(1.0), _mm_mul_ps(dist_sq, _mm_sqrt_ps(dist_sq))), None, None),
+            };
+            let sums = &mut self.sums;
+            for (axis, diff) in [dx, dy, dz].into_iter().enumerate() {
+                let acc = sums.sep[axis][half..].as_mut_ptr();
+                _mm_storeu_ps(acc, _mm_add_ps(_mm_loadu_ps(acc), _mm_and_ps(sep, _mm_mul_ps(diff, sep_scale))));
+                let acc = sums.align[axis][half..].as_mut_ptr();
+                let mut vel = _mm_loadu_ps(self.vel[axis][half..].as_ptr());
+                if let Some(scale) = align_scale {
+                    vel = _mm_mul_ps(vel, scale);
+                }
+                _mm_storeu_ps(acc, _mm_add_ps(_mm_loadu_ps(acc), _mm_and_ps(align, vel)));
+                let acc = sums.cohere[axis][half..].as_mut_ptr();
+                let mut pos = _mm_loadu_ps(self.pos[axis][half..].as_ptr());
+                if let Some(scale) = cohere_scale {
+                    pos = _mm_mul_ps(pos, scale);
+                }
+                _mm_storeu_ps(acc, _mm_add_ps(_mm_loadu_ps(acc), _mm_and_ps(cohere, pos)));
+            }
+            
+            sums.counts[0] += _mm_movemask_ps(sep).count_ones();
+            sums.counts[1] += _mm_movemask_ps(align).count_ones();
+            sums.counts[2] += _mm_movemask_ps(cohere).count_ones();
+        }
+    }
+    
+    #[cfg(target_arch = "aarch64")]
+    #[target_feature(enable = "neon")]
+    unsafe fn flush_neon(&mut self) {
+        use std::arch::aarch64::*;
+        
+        // Masked accumulate, lanes outside `mask` keep their value
+        #[inline(always)]
+        unsafe fn add_masked(acc: *mut f32, mask: uint32x4_t, value: float32x4_t) {
+            let value = vreinterpretq_f32_u32(vandq_u32(mask, vreinterpretq_u32_f32(value)));
+            vst1q_f32(acc, vaddq_f32(vld1q_f32(acc), value));
+        }
+        
+        for half in [0, 4] {
+            let dx = vsubq_f32(vdupq_n_f32(self.position.x), vld1q_f32(self.pos[0][half..].as_ptr()));
+            let dy = vsubq_f32(vdupq_n_f32(self.position.y), vld1q_f32(self.pos[1][half..].as_ptr()));
+            let dz = vsubq_f32(vdupq_n_f32(self.position.z), vld1q_f32(self.pos[2][half..].as_ptr()));
+            let dist_sq = vaddq_f32(vmulq_f32(dx, dx), vaddq_f32(vmulq_f32(dy, dy), vmulq_f32(dz, dz)));
+            
+            let valid = vcgeq_f32(dist_sq, vdupq_n_f32(f32::EPSILON));
+            let sep = vandq_u32(valid, vcltq_f32(dist_sq, vdupq_n_f32(self.radii.sep_dist_sq)));
+            let align = vandq_u32(valid, vcltq_f32(dist_sq, vdupq_n_f32(self.radii.align_dist_sq)));
+            let cohere = vandq_u32(valid, vcltq_f32(dist_sq, vdupq_n_f32(self.radii.cohere_dist_sq)));
+            
+            // `diff` points away from the neighbour, so it's ahead when `diff` points backwards
+            let (sep, align, cohere) = match self.view {
+                Some(view) => {
+                    let heading_dot = vaddq_f32(
+                        vmulq_n_f32(dx, view.heading.x),
+                        vaddq_f32(vmulq_n_f32(dy, view.heading.y), vmulq_n_f32(dz, view.heading.z)),
+                    );
+                    let ahead = vnegq_f32(heading_dot);
+                    let dist = vsqrtq_f32(dist_sq);
+                    let [sep_cos, align_cos, cohere_cos] = view.cos_half;
+                    (
+                        vandq_u32(sep, vcgeq_f32(ahead, vmulq_n_f32(dist, sep_cos))),
+                        vandq_u32(align, vcgeq_f32(ahead, vmulq_n_f32(dist, align_cos))),
+                        vandq_u32(cohere, vcgeq_f32(ahead, vmulq_n_f32(dist, cohere_cos))),
+                    )
+                }
+                None => (sep, align, cohere),
+            };
+            
+            // Masked out lanes may divide by zero, the mask clears the resulting NaNs
+            let (sep_scale, align_scale, cohere_scale) = match &self.falloff {
+                Some(falloff) => {
+                    let (mut lane_dist_sq, mut lane_dist) = ([0.0; 4], [0.0; 4]);
+                    vst1q_f32(lane_dist_sq.as_mut_ptr(), dist_sq);
+                    vst1q_f32(lane_dist.as_mut_ptr(), vsqrtq_f32(dist_sq));
+                    let [sep_s, sep_w, align_w, cohere_w] = falloff.weights(&lane_dist_sq, &lane_dist);
+                    let [sep_w, align_w, cohere_w] = [sep_w, align_w, cohere_w].map(|w| vld1q_f32(w.as_ptr()));
+                    for (rule, (mask, w)) in [(sep, sep_w), (align, align_w), (cohere, cohere_w)].into_iter().enumerate() {
+                        add_masked(self.sums.weights[rule][half..].as_mut_ptr(), mask, w);
+                    }
+                    (vld1q_f32(sep_s.as_ptr()), Some(align_w), Some(cohere_w))
+                }
+                None => (vdivq_f32(vdupq_n_f32(1.0), vmulq_f32(dist_sq, vsqrtq_f32(dist_sq))), None, None),
+            };
+            let sums = &mut self.sums;
+            for (axis, diff) in [dx, dy, dz].into_iter().enumerate() {
+                add_masked(sums.sep[axis][half..].as_mut_ptr(), sep, vmulq_f32(diff, sep_scale));
+                let mut vel = vld1q_f32(self.vel[axis][half..].as_ptr());
+                if let Some(scale) = align_scale {
+                    vel = vmulq_f32(vel, scale);
+                }
+                add_masked(sums.align[axis][half..].as_mut_ptr(), align, vel);
+                let mut pos = vld1q_f32(self.pos[axis][half..].as_ptr());
+                if let Some(scale) = cohere_scale {
+                    pos = vmulq_f32(pos, scale);
+                }
+                add_masked(sums.cohere[axis][half..].as_mut_ptr(), cohere, pos);
+            }
+            
+            sums.counts[0] += vaddvq_u32(vshrq_n_u32::<31>(sep));
+            sums.counts[1] += vaddvq_u32(vshrq_n_u32::<31>(align));
+            sums.counts[2] += vaddvq_u32(vshrq_n_u32::<31>(cohere));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::Rng, FALLOFF_CURVE_SAMPLES};
+    
+    const KERNELS: [NeighborKernel; 4] = [NeighborKernel::Scalar, NeighborKernel::Sse, NeighborKernel::Avx, NeighborKernel::Neon];
+    
+    fn accumulate(
+        kernel: NeighborKernel,
+        position: Vec3,
+        neighbors: &[(Vec3, Vec3)],
+        view: Option<NeighborView>,
+        falloff: Option<NeighborFalloff>,
+    ) -> NeighborSums {
+        let radii = NeighborRadii { sep_dist_sq: 400.0, align_dist_sq: 1600.0, cohere_dist_sq: 2500.0 };
+        let mut batch = NeighborBatch::new(kernel, position, radii, view, falloff);
+        for &(pos, vel) in neighbors {
+            batch.push(pos, vel);
+        }
+        batch.finish()
+    }
+    
+    fn assert_close(kernel: NeighborKernel, what: &str, actual: Vec3, expected: Vec3) {
+        let tolerance = 1e-4 * (1.0 + expected.abs().max_element());
+        assert!(
+            (actual - expected).abs().max_element() <= tolerance,
+            "{kernel:?} {what}: {actual} differs from scalar {expected}",
+        );
+    }
+    
+    fn assert_kernels_match(view: Option<NeighborView>, falloff: Option<NeighborFalloff>) {
+        let mut rng = Rng(0x9e3779b9);
+        // Full batches, partial ones and a mix of both
+        for count in [0, 1, 3, 7, 8, 9, 16, 21, 64] {
+            let position = rng.vec3(100.0);
+            let mut neighbors: Vec<_> = (0..count).map(|_| (position + rng.vec3(60.0), rng.vec3(200.0))).collect();
+            // Boids on top of each other are skipped by every kernel
+            if count > 2 {
+                neighbors[1].0 = position;
+            }
+            
+            let expected = accumulate(NeighborKernel::Scalar, position, &neighbors, view, falloff);
+            for kernel in KERNELS.into_iter().filter(|kernel| kernel.is_supported()) {
+                let sums = accumulate(kernel, position, &neighbors, view, falloff);
+                assert_eq!(sums.counts, expected.counts, "{kernel:?} counts with {count} neighbours");
+                assert_close(kernel, "separation", sums.sep_sum, expected.sep_sum);
+                assert_close(kernel, "alignment", sums.align_sum, expected.align_sum);
+                assert_close(kernel, "cohesion", sums.cohere_sum, expected.cohere_sum);
+                assert_close(kernel, "weights", Vec3::from_array(sums.weights), Vec3::from_array(expected.weights));
+            }
+        }
+    }
+    
+    #[test]
+    fn kernels_match_scalar() {
+        assert_kernels_match(None, None);
+    }
+    
+    #[test]
+    fn kernels_match_scalar_with_view() {
+        let view = NeighborView { heading: Vec3::new(1.0, 2.0, -0.5).normalize(), cos_half: [-0.5, 0.0, 0.7] };
+        assert_kernels_match(Some(view), None);
+    }
+    
+    #[test]
+    fn kernels_match_scalar_with_falloff() {
+        let curve = std::array::from_fn(|i| 1.0 - i as f32 / (FALLOFF_CURVE_SAMPLES - 1) as f32);
+        for rules in [
+            [Falloff::None, Falloff::Linear, Falloff::Smoothstep],
+            [Falloff::InverseSquare, Falloff::Curve(curve), Falloff::Linear],
+        ] {
+            let falloff = NeighborFalloff { rules, inv_radius: [1.0 / 20.0, 1.0 / 40.0, 1.0 / 50.0] };
+            assert_kernels_match(None, Some(falloff));
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use glam::*;
+use crate::{BoidParams, BoundsInstance, FlockParams, RelationKind, SpatialIndexKind};
+
+pub mod kernel;
+pub mod ultra;
+
+pub use kernel::NeighborKernel;
+pub use ultra::*;
+
+/// Space boids are simulated in. 2D boids live on the xy plane with z fixed at 0.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum Dimensions {
+    D2,
+    #[default]
+    D3,
+}
+
+// Core algorithm trait for extensibility
+pub trait BoidAlgorithm {
+    /// Process all boids and update their forces.
+    /// Every boid is simulated with the properties and target of the flock its `flock` index points at.
+    /// `delta` is the time in seconds the forces will be integrated over, resulting forces are accelerations (units per second squared).
+    fn process_boids(&mut self, boids_data: &mut [BoidInstance], flocks: &[FlockInstance], delta: f32);
+}
+
+// Lightweight boid instance for algorithm processing
+#[repr(C)]
+#[derive(Clone, Debug)]
+pub struct BoidInstance {
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub properties: BoidParams,
+    pub force: Vec3,
+    /// Extra speed, relative to `max_speed`, the boid may currently reach. Set while fleeing, fading afterwards.
+    pub speed_boost: f32,
+    /// Index into the flock slice passed alongside the boids.
+    pub flock: u32,
+    /// Identifier of the boid within its flock, kept for as long as the boid lives. Picks its own spot in the wander noise.
+    pub id: u32,
+}
+
+impl BoidInstance {
+    #[inline(always)]
+    pub fn new(position: Vec3, velocity: Vec3, properties: BoidParams, flock: u32) -> Self {
+        Self {
+            position,
+            velocity,
+            properties,
+            force: Vec3::ZERO,
+            speed_boost: 0.0,
+            flock,
+            id: 0,
+        }
+    }
+}
+
+// Per-flock data for algorithm processing
+#[derive(Clone, Debug)]
+pub struct FlockInstance {
+    pub properties: FlockParams,
+    pub target: Option<Vec3>,
+    /// How this flock reacts to other flocks, flocks without an entry are ignored.
+    pub relations: Vec<FlockRelationInstance>,
+    /// Spatial structure used to find neighbours within this flock.
+    pub spatial_index: SpatialIndexKind,
+    /// Volume the flock's boids are kept in, `None` lets them roam freely.
+    pub bounds: Option<BoundsInstance>,
+    /// Seed of the noise the flock's boids wander along, the same seed wanders the same way.
+    pub seed: u32,
+    /// Seconds the flock has been simulated for, its boids wander along the noise as time passes.
+    pub time: f64,
+}
+
+impl FlockInstance {
+    #[inline(always)]
+    pub fn new(properties: FlockParams, target: Option<Vec3>) -> Self {
+        Self {
+            properties,
+            target,
+            relations: Vec::new(),
+            spatial_index: SpatialIndexKind::default(),
+            bounds: None,
+            seed: 0,
+            time: 0.0,
+        }
+    }
+}
+
+// One-sided rule from the owning flock towards another flock
+#[derive(Clone, Copy, Debug)]
+pub struct FlockRelationInstance {
+    /// Index into the flock slice passed alongside the boids.
+    pub flock: u32,
+    pub kind: RelationKind,
+    /// Distance (squared) within which the other flock is reacted to.
+    pub distance: f32,
+    pub weight: f32,
+}
@@ -0,0 +1,1099 @@
+use std::f32::consts::TAU;
+
+use glam::*;
+use rayon::prelude::*;
+use super::kernel::{separation_scale, NeighborBatch, NeighborFalloff, NeighborKernel, NeighborRadii, NeighborView};
+use super::{BoidAlgorithm, BoidInstance, Dimensions, FlockInstance};
+use crate::noise::{hash, simplex3};
+use crate::spatial::{SpatialIndex, SpatialIndexKind, SpatialStructure, UniformGrid};
+use crate::{BoundsInstance, BoundsMode, Falloff, NeighborMode, ObstacleInstance, PredatorInstance, RelationKind, MAX_NEAREST_NEIGHBORS};
+
+// Per-flock simulation state, refreshed every tick
+#[derive(Clone, Copy)]
+struct FlockState {
+    sep_dist_sq: f32,
+    align_dist_sq: f32,
+    cohere_dist_sq: f32,
+    // Cosine of half the view angle per rule, `None` when no rule has a blind spot
+    view_cos: Option<[f32; 3]>,
+    // Amount of nearest neighbours boids react to in topological mode, `None` in metric mode
+    nearest: Option<usize>,
+    // `None` when no rule has a falloff
+    falloff: Option<NeighborFalloff>,
+    // Falloff of separation, also used for boids of other flocks to keep a distance from
+    sep_falloff: Falloff,
+    max_radius: f32,
+    target: Option<Vec3>,
+    spatial_index: SpatialIndexKind,
+    // Only steering bounds affect forces, wrapping and bouncing happens when integrating
+    bounds: Option<BoundsInstance>,
+    // Seed of the wander noise
+    seed: u32,
+    // Seconds the flock has been simulated for, its boids wander along the noise as it passes
+    time: f64,
+    // SoA slots owned by this flock
+    start: u32,
+    end: u32,
+    // Range into `UltraBoidProcessor::relations`
+    relation_start: u32,
+    relation_end: u32,
+}
+
+impl FlockState {
+    #[inline(always)]
+    fn new(flock: &FlockInstance) -> Self {
+        let props = &flock.properties;
+        // Topological flocks align and cohere with their nearest neighbours up to the search distance
+        let (align_dist_sq, cohere_dist_sq, nearest) = match props.neighbor_mode {
+            NeighborMode::Metric => (props.goal_alignment, props.goal_cohesion, None),
+            NeighborMode::Topological { count, max_distance } => {
+                let count = count.clamp(1, MAX_NEAREST_NEIGHBORS) as usize;
+                (max_distance, max_distance, Some(count))
+            }
+        };
+        // Calculate max interaction radius for spatial queries
+        let max_radius = f32::max(
+            f32::max(props.goal_seperation.sqrt(), align_dist_sq.sqrt()),
+            cohere_dist_sq.sqrt()
+        );
+        let view_angles = [props.seperation_view_angle, props.alignment_view_angle, props.cohesion_view_angle];
+        // A full turn sees everything, below -1 so rounding can't hide neighbours right behind
+        let view_cos = view_angles.iter().any(|&angle| angle < TAU)
+            .then(|| view_angles.map(|angle| if angle < TAU { (angle.max(0.0) * 0.5).cos() } else { -2.0 }));
+        let falloffs = [props.seperation_falloff, props.alignment_falloff, props.cohesion_falloff];
+        let falloff = falloffs.iter().any(|falloff| *falloff != Falloff::None).then(|| NeighborFalloff {
+            rules: falloffs,
+            inv_radius: [props.goal_seperation, align_dist_sq, cohere_dist_sq].map(|dist_sq| 1.0 / dist_sq.sqrt()),
+        });
+        Self {
+            sep_dist_sq: props.goal_seperation,
+            align_dist_sq,
+            cohere_dist_sq,
+            view_cos,
+            nearest,
+            falloff,
+            sep_falloff: props.seperation_falloff,
+            max_radius,
+            target: flock.target,
+            spatial_index: flock.spatial_index,
+            bounds: flock.bounds.filter(|bounds| bounds.mode == BoundsMode::Steer),
+            seed: flock.seed,
+            time: flock.time,
+            start: 0,
+            end: 0,
+            relation_start: 0,
+            relation_end: 0,
+        }
+    }
+}
+
+// Share of `max_speed` a fleeing boid's speed boost fades by per second, once the predator is gone
+const SPEED_BOOST_FADE: f32 = 1.0;
+
+// Distance travelled through the wander noise per second, the higher the more often boids change their mind
+const WANDER_RATE: f64 = 0.5;
+// Size of the region of the noise boids pick their spot in, far enough apart to wander on their own
+const WANDER_SPREAD: f32 = 1024.0;
+// Wander circle ahead of the boid, relative to its distance
+const WANDER_RADIUS: f32 = 0.5;
+
+// Active relation from the owning flock towards `flock`
+#[derive(Clone, Copy)]
+struct RelationState {
+    flock: u32,
+    kind: RelationKind,
+    distance: f32,
+    distance_sq: f32,
+    weight: f32,
+}
+
+// Cache-aligned Structure of Arrays for SIMD processing
+#[repr(C, align(64))]
+pub struct UltraBoidProcessor {
+    // SoA layout for vectorization
+    positions_x: Vec<f32>,
+    positions_y: Vec<f32>,
+    positions_z: Vec<f32>,
+    velocities_x: Vec<f32>,
+    velocities_y: Vec<f32>,
+    velocities_z: Vec<f32>,
+    forces_x: Vec<f32>,
+    forces_y: Vec<f32>,
+    forces_z: Vec<f32>,
+    // Speed boost from predators this tick
+    speed_boosts: Vec<f32>,
+    
+    // Properties arrays
+    max_speeds: Vec<f32>,
+    max_forces: Vec<f32>,
+    separations: Vec<f32>,
+    alignments: Vec<f32>,
+    cohesions: Vec<f32>,
+    targetings: Vec<f32>,
+    avoidances: Vec<f32>,
+    flees: Vec<f32>,
+    wanders: Vec<f32>,
+    flock_indices: Vec<u32>,
+    boid_ids: Vec<u32>,
+    
+    // SoA slot -> index into the boids passed to `load_boids`, slots are grouped by flock, then by cell
+    slots: Vec<u32>,
+    sorted_slots: Vec<u32>,
+    // Orders each flock's slots by cell, so neighbours end up next to each other in the SoA buffers
+    cell_sorter: UniformGrid,
+    
+    // One entry per flock, indexed by `flock_indices`
+    flocks: Vec<FlockState>,
+    // Relations of all flocks, flocks without a relation towards each other are isolated
+    relations: Vec<RelationState>,
+    // One spatial structure per flock, built over the positions of that flock's slots
+    spatial_indices: Vec<SpatialIndex>,
+    positions: Vec<Vec3>,
+    // Obstacles every boid steers around, regardless of its flock
+    obstacles: Vec<ObstacleInstance>,
+    // Predators boids of every flock flee from
+    predators: Vec<PredatorInstance>,
+    
+    dimensions: Dimensions,
+    // Instruction set for the neighbour loop, always supported by the running CPU
+    kernel: NeighborKernel,
+    capacity: usize,
+    // Hard limit the SoA buffers never grow past, `None` grows on demand
+    max_capacity: Option<usize>,
+    count: usize,
+    // Boids left out of the last `load_boids` because of `max_capacity`
+    dropped: usize,
+    // Seconds simulated by the current `process_boids` call
+    delta: f32,
+}
+
+impl UltraBoidProcessor {
+    /// Creates a processor with room for `capacity` boids, simulating in `dimensions`.
+    /// Grid cell sizes follow the perception radii of the processed flocks.
+    pub fn new(capacity: usize, dimensions: Dimensions) -> Self {
+        let mut processor = Self {
+            positions_x: Vec::with_capacity(capacity),
+            positions_y: Vec::with_capacity(capacity),
+            positions_z: Vec::with_capacity(capacity),
+            velocities_x: Vec::with_capacity(capacity),
+            velocities_y: Vec::with_capacity(capacity),
+            velocities_z: Vec::with_capacity(capacity),
+            forces_x: Vec::with_capacity(capacity),
+            forces_y: Vec::with_capacity(capacity),
+            forces_z: Vec::with_capacity(capacity),
+            speed_boosts: Vec::with_capacity(capacity),
+            max_speeds: Vec::with_capacity(capacity),
+            max_forces: Vec::with_capacity(capacity),
+            separations: Vec::with_capacity(capacity),
+            alignments: Vec::with_capacity(capacity),
+            cohesions: Vec::with_capacity(capacity),
+            targetings: Vec::with_capacity(capacity),
+            avoidances: Vec::with_capacity(capacity),
+            flees: Vec::with_capacity(capacity),
+            wanders: Vec::with_capacity(capacity),
+            flock_indices: Vec::with_capacity(capacity),
+            boid_ids: Vec::with_capacity(capacity),
+            slots: Vec::with_capacity(capacity),
+            sorted_slots: Vec::with_capacity(capacity),
+            cell_sorter: UniformGrid::new(1.0, dimensions),
+            flocks: Vec::new(),
+            relations: Vec::new(),
+            spatial_indices: Vec::new(),
+            positions: Vec::with_capacity(capacity),
+            obstacles: Vec::new(),
+            predators: Vec::new(),
+            dimensions,
+            kernel: NeighborKernel::detect(),
+            capacity,
+            max_capacity: None,
+            count: 0,
+            dropped: 0,
+            delta: 1.0 / 60.0,
+        };
+        
+        // Pre-allocate to avoid runtime allocation
+        processor.resize_to_capacity();
+        processor
+    }
+    
+    /// Current size of the SoA buffers, grows on demand up to [`Self::max_capacity`].
+    #[inline(always)]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    
+    #[inline(always)]
+    pub fn max_capacity(&self) -> Option<usize> {
+        self.max_capacity
+    }
+    
+    /// Limits how many boids get simulated, `None` removes the limit.
+    /// Boids past the limit get a zero force until the limit is raised.
+    pub fn set_max_capacity(&mut self, max_capacity: Option<usize>) {
+        self.max_capacity = max_capacity;
+    }
+    
+    /// Instruction set used for the neighbour loop, detected when the processor is created.
+    #[inline(always)]
+    pub fn kernel(&self) -> NeighborKernel {
+        self.kernel
+    }
+    
+    /// Forces a neighbour kernel, falling back to [`NeighborKernel::Scalar`] if the CPU doesn't support it.
+    pub fn set_kernel(&mut self, kernel: NeighborKernel) {
+        self.kernel = if kernel.is_supported() { kernel } else { NeighborKernel::Scalar };
+    }
+    
+    /// Amount of boids that were not simulated by the last `process_boids` call because of [`Self::max_capacity`].
+    #[inline(always)]
+    pub fn dropped_count(&self) -> usize {
+        self.dropped
+    }
+    
+    /// Grows the SoA buffers to fit at least `count` boids, respecting [`Self::max_capacity`].
+    pub fn reserve(&mut self, count: usize) {
+        let count = count.min(self.max_capacity.unwrap_or(usize::MAX));
+        if count <= self.capacity { return; }
+        
+        // Grow geometrically so slowly spawning flocks don't reallocate every tick
+        self.capacity = count.max(self.capacity * 2).min(self.max_capacity.unwrap_or(usize::MAX));
+        self.resize_to_capacity();
+    }
+    
+    #[inline(always)]
+    fn resize_to_capacity(&mut self) {
+        self.positions_x.resize(self.capacity, 0.0);
+        self.positions_y.resize(self.capacity, 0.0);
+        self.positions_z.resize(self.capacity, 0.0);
+        self.velocities_x.resize(self.capacity, 0.0);
+        self.velocities_y.resize(self.capacity, 0.0);
+        self.velocities_z.resize(self.capacity, 0.0);
+        self.forces_x.resize(self.capacity, 0.0);
+        self.forces_y.resize(self.capacity, 0.0);
+        self.forces_z.resize(self.capacity, 0.0);
+        self.speed_boosts.resize(self.capacity, 0.0);
+        self.max_speeds.resize(self.capacity, 240.0);
+        self.max_forces.resize(self.capacity, 3600.0);
+        self.separations.resize(self.capacity, 1.2);
+        self.alignments.resize(self.capacity, 1.5);
+        self.cohesions.resize(self.capacity, 1.0);
+        self.targetings.resize(self.capacity, 0.8);
+        self.avoidances.resize(self.capacity, 2.0);
+        self.flees.resize(self.capacity, 3.0);
+        self.wanders.resize(self.capacity, 0.0);
+        self.flock_indices.resize(self.capacity, 0);
+        self.boid_ids.resize(self.capacity, 0);
+    }
+    
+    /// Loads boids into the SoA buffers, grouped by flock and sorted by cell within each flock.
+    /// Expects [`Self::load_flocks`] to have been called first, [`Self::store_forces`] maps results back to the order of `boids`.
+    #[inline(always)]
+    pub fn load_boids(&mut self, boids: &[BoidInstance]) {
+        self.reserve(boids.len());
+        // The buffers may have grown past a limit that was lowered since
+        let limit = self.capacity.min(self.max_capacity.unwrap_or(usize::MAX));
+        self.count = boids.len().min(limit);
+        self.dropped = boids.len() - self.count;
+        
+        // Counting sort by flock, so every flock owns a contiguous slot range
+        for flock in self.flocks.iter_mut() {
+            flock.start = 0;
+            flock.end = 0;
+        }
+        for boid in boids {
+            self.flocks[boid.flock as usize].end += 1;
+        }
+        let mut offset = 0;
+        for flock in self.flocks.iter_mut() {
+            flock.start = offset;
+            offset += flock.end;
+            flock.end = flock.start;
+        }
+        self.slots.resize(boids.len(), 0);
+        for (i, boid) in boids.iter().enumerate() {
+            let flock = &mut self.flocks[boid.flock as usize];
+            self.slots[flock.end as usize] = i as u32;
+            flock.end += 1;
+        }
+        self.sort_slots_by_cell(boids);
+        
+        // Boids past the capacity are dropped from the end of the last flocks
+        let count = self.count as u32;
+        for flock in self.flocks.iter_mut() {
+            flock.start = flock.start.min(count);
+            flock.end = flock.end.min(count);
+        }
+        
+        // Bulk load with unsafe for maximum performance
+        unsafe {
+            for i in 0..self.count {
+                let boid = boids.get_unchecked(*self.slots.get_unchecked(i) as usize);
+                *self.positions_x.get_unchecked_mut(i) = boid.position.x;
+                *self.positions_y.get_unchecked_mut(i) = boid.position.y;
+                *self.positions_z.get_unchecked_mut(i) = boid.position.z;
+                *self.velocities_x.get_unchecked_mut(i) = boid.velocity.x;
+                *self.velocities_y.get_unchecked_mut(i) = boid.velocity.y;
+                *self.velocities_z.get_unchecked_mut(i) = boid.velocity.z;
+                *self.max_speeds.get_unchecked_mut(i) = boid.properties.max_speed;
+                *self.max_forces.get_unchecked_mut(i) = boid.properties.max_force;
+                *self.separations.get_unchecked_mut(i) = boid.properties.seperation;
+                *self.alignments.get_unchecked_mut(i) = boid.properties.alignment;
+                *self.cohesions.get_unchecked_mut(i) = boid.properties.cohesion;
+                *self.targetings.get_unchecked_mut(i) = boid.properties.targeting;
+                *self.avoidances.get_unchecked_mut(i) = boid.properties.avoidance;
+                *self.flees.get_unchecked_mut(i) = boid.properties.flee;
+                *self.wanders.get_unchecked_mut(i) = boid.properties.wander;
+                *self.flock_indices.get_unchecked_mut(i) = boid.flock;
+                *self.boid_ids.get_unchecked_mut(i) = boid.id;
+            }
+        }
+        
+        // Zero out forces
+        unsafe {
+            std::ptr::write_bytes(self.forces_x.as_mut_ptr(), 0, self.count);
+            std::ptr::write_bytes(self.forces_y.as_mut_ptr(), 0, self.count);
+            std::ptr::write_bytes(self.forces_z.as_mut_ptr(), 0, self.count);
+            std::ptr::write_bytes(self.speed_boosts.as_mut_ptr(), 0, self.count);
+        }
+    }
+    
+    // Counting sorts every flock's slot range by grid cell, one cell per perception radius
+    fn sort_slots_by_cell(&mut self, boids: &[BoidInstance]) {
+        let mut positions = std::mem::take(&mut self.positions);
+        self.sorted_slots.clear();
+        self.sorted_slots.extend_from_slice(&self.slots);
+        
+        for flock in &self.flocks {
+            let (start, end) = (flock.start as usize, flock.end as usize);
+            // A degenerate radius has no meaningful cells, keep registration order
+            if end - start < 2 || flock.max_radius <= f32::EPSILON || !flock.max_radius.is_finite() { continue; }
+            
+            positions.clear();
+            positions.extend(self.slots[start..end].iter().map(|&slot| boids[slot as usize].position));
+            self.cell_sorter.set_cell_size(flock.max_radius);
+            self.cell_sorter.rebuild(&positions);
+            
+            let flock_slots = &self.slots[start..end];
+            for (sorted, &local_idx) in self.sorted_slots[start..end].iter_mut().zip(self.cell_sorter.sorted_indices()) {
+                *sorted = flock_slots[local_idx as usize];
+            }
+        }
+        
+        std::mem::swap(&mut self.slots, &mut self.sorted_slots);
+        self.positions = positions;
+    }
+    
+    #[inline(always)]
+    pub fn load_flocks(&mut self, flocks: &[FlockInstance]) {
+        self.flocks.clear();
+        self.flocks.extend(flocks.iter().map(FlockState::new));
+        
+        // Flocks are isolated unless a relation says otherwise
+        let flock_count = flocks.len();
+        self.relations.clear();
+        for (own, flock) in flocks.iter().enumerate() {
+            let relation_start = self.relations.len() as u32;
+            for relation in &flock.relations {
+                let other = relation.flock as usize;
+                if other == own || other >= flock_count || relation.kind == RelationKind::Ignore {
+                    continue;
+                }
+                self.relations.push(RelationState {
+                    flock: relation.flock,
+                    kind: relation.kind,
+                    distance: relation.distance.sqrt(),
+                    distance_sq: relation.distance,
+                    weight: relation.weight,
+                });
+            }
+            let state = &mut self.flocks[own];
+            state.relation_start = relation_start;
+            state.relation_end = self.relations.len() as u32;
+        }
+        
+        // Keep the spatial structures around between ticks, only replacing them when a flock switches kind
+        self.spatial_indices.truncate(flock_count);
+        for (i, flock) in self.flocks.iter().enumerate() {
+            match self.spatial_indices.get_mut(i) {
+                Some(index) if index.kind() == flock.spatial_index => {}
+                Some(index) => *index = SpatialIndex::new(flock.spatial_index, 1.0, self.dimensions),
+                None => self.spatial_indices.push(SpatialIndex::new(flock.spatial_index, 1.0, self.dimensions)),
+            }
+            
+            // One cell per perception radius keeps queries at the directly adjacent cells
+            let index = &mut self.spatial_indices[i];
+            if flock.max_radius > f32::EPSILON && flock.max_radius != index.get_cell_size() {
+                index.set_cell_size(flock.max_radius);
+            }
+        }
+    }
+    
+    /// Replaces the obstacles boids steer around, they stay until replaced again.
+    pub fn load_obstacles(&mut self, obstacles: impl IntoIterator<Item = ObstacleInstance>) {
+        self.obstacles.clear();
+        self.obstacles.extend(obstacles);
+    }
+    
+    /// Replaces the predators boids flee from, they stay until replaced again.
+    pub fn load_predators(&mut self, predators: impl IntoIterator<Item = PredatorInstance>) {
+        self.predators.clear();
+        self.predators.extend(predators);
+    }
+    
+    /// Writes the forces of the last `process_boids` call back to `boids`.
+    /// Speed boosts are raised by nearby predators, and otherwise fade over time.
+    #[inline(always)]
+    pub fn store_forces(&self, boids: &mut [BoidInstance]) {
+        // Dropped boids must not keep steering with a stale force
+        let fade = SPEED_BOOST_FADE * self.delta;
+        for boid in boids.iter_mut() {
+            boid.force = Vec3::ZERO;
+            boid.speed_boost = (boid.speed_boost - fade).max(0.0);
+        }
+        
+        unsafe {
+            for i in 0..self.count {
+                let boid = boids.get_unchecked_mut(*self.slots.get_unchecked(i) as usize);
+                boid.force = Vec3::new(
+                    *self.forces_x.get_unchecked(i),
+                    *self.forces_y.get_unchecked(i),
+                    *self.forces_z.get_unchecked(i),
+                );
+                boid.speed_boost = boid.speed_boost.max(*self.speed_boosts.get_unchecked(i));
+            }
+        }
+    }
+    
+    // Rebuilds every flock's spatial structure over its own slot range
+    fn rebuild_spatial_indices(&mut self) {
+        let mut positions = std::mem::take(&mut self.positions);
+        positions.clear();
+        positions.extend((0..self.count).map(|i| self.get_position(i)));
+        self.positions = positions;
+        
+        for (flock, index) in self.flocks.iter().zip(self.spatial_indices.iter_mut()) {
+            index.rebuild(&self.positions[flock.start as usize..flock.end as usize]);
+        }
+    }
+    
+    #[inline(always)]
+    fn get_position(&self, idx: usize) -> Vec3 {
+        unsafe {
+            Vec3::new(
+                *self.positions_x.get_unchecked(idx),
+                *self.positions_y.get_unchecked(idx),
+                *self.positions_z.get_unchecked(idx),
+            )
+        }
+    }
+    
+    #[inline(always)]
+    fn get_velocity(&self, idx: usize) -> Vec3 {
+        unsafe {
+            Vec3::new(
+                *self.velocities_x.get_unchecked(idx),
+                *self.velocities_y.get_unchecked(idx),
+                *self.velocities_z.get_unchecked(idx),
+            )
+        }
+    }
+}
+
+impl BoidAlgorithm for UltraBoidProcessor {
+    fn process_boids(&mut self, boids_data: &mut [BoidInstance], flocks: &[FlockInstance], delta: f32) {
+        if boids_data.is_empty() { self.dropped = 0; return; }
+        if flocks.is_empty() || delta <= 0.0 { return; }
+        self.delta = delta;
+        
+        // Load flocks into per-flock state, boids into SoA layout grouped by flock
+        self.load_flocks(flocks);
+        self.load_boids(boids_data);
+        
+        // Rebuild spatial structures
+        self.rebuild_spatial_indices();
+        
+        // Parallel force calculation with optimal chunk size
+        const CHUNK_SIZE: usize = 256; // L2 cache optimized
+        
+        // Move the force buffers out so workers can write disjoint chunks while reading the rest of the processor
+        let mut forces_x = std::mem::take(&mut self.forces_x);
+        let mut forces_y = std::mem::take(&mut self.forces_y);
+        let mut forces_z = std::mem::take(&mut self.forces_z);
+        let mut speed_boosts = std::mem::take(&mut self.speed_boosts);
+        
+        let count = self.count;
+        let processor = &*self;
+        forces_x[..count].par_chunks_mut(CHUNK_SIZE)
+            .zip(forces_y[..count].par_chunks_mut(CHUNK_SIZE))
+            .zip(forces_z[..count].par_chunks_mut(CHUNK_SIZE))
+            .zip(speed_boosts[..count].par_chunks_mut(CHUNK_SIZE))
+            .enumerate()
+            .for_each(|(chunk_idx, (((fx, fy), fz), boosts))| {
+                let start = chunk_idx * CHUNK_SIZE;
+                let outputs = fx.iter_mut().zip(fy.iter_mut()).zip(fz.iter_mut()).zip(boosts.iter_mut());
+                for (i, (((fx, fy), fz), boost)) in outputs.enumerate() {
+                    let (force, speed_boost) = processor.calculate_boid_force(start + i);
+                    *fx = force.x;
+                    *fy = force.y;
+                    *fz = force.z;
+                    *boost = speed_boost;
+                }
+            });
+        
+        self.forces_x = forces_x;
+        self.forces_y = forces_y;
+        self.forces_z = forces_z;
+        self.speed_boosts = speed_boosts;
+        
+        // Store forces back to boids
+        self.store_forces(boids_data);
+    }
+}
+
+impl UltraBoidProcessor {
+    // Steering acceleration of a boid, and the speed boost it gets from fleeing predators
+    #[inline(always)]
+    fn calculate_boid_force(&self, boid_idx: usize) -> (Vec3, f32) {
+        let pos = self.get_position(boid_idx);
+        let vel = self.get_velocity(boid_idx);
+        let flock_idx = unsafe { *self.flock_indices.get_unchecked(boid_idx) } as usize;
+        let flock = &self.flocks[flock_idx];
+        
+        // Same flock neighbours go through the vectorised kernel, gathered from the SoA buffers
+        let flock_start = flock.start as usize;
+        let radii = NeighborRadii {
+            sep_dist_sq: flock.sep_dist_sq,
+            align_dist_sq: flock.align_dist_sq,
+            cohere_dist_sq: flock.cohere_dist_sq,
+        };
+        // Standing boids have no heading to have a blind spot behind
+        let view = flock.view_cos.and_then(|cos_half| {
+            Some(NeighborView { heading: vel.try_normalize()?, cos_half })
+        });
+        let mut batch = NeighborBatch::new(self.kernel, pos, radii, view, flock.falloff);
+        match flock.nearest {
+            None => {
+                self.spatial_indices[flock_idx].for_each_neighbor(pos, flock.max_radius, |local_indices| {
+                    for &local_idx in local_indices {
+                        let neighbor_idx = flock_start + local_idx as usize;
+                        // The boid itself overlaps its own position and gets skipped by the kernel
+                        batch.push(self.get_position(neighbor_idx), self.get_velocity(neighbor_idx));
+                    }
+                });
+            }
+            Some(count) => {
+                // Nearest visible neighbours sorted by distance, the farthest gets dropped once full.
+                // Boids in the blind spot of every rule don't take up a place
+                let widest_view = view.map(|view| (view.heading, view.cos_half.into_iter().fold(1.0, f32::min)));
+                let mut nearest = [(f32::INFINITY, 0u32); MAX_NEAREST_NEIGHBORS as usize];
+                let mut len = 0;
+                self.spatial_indices[flock_idx].for_each_neighbor(pos, flock.max_radius, |local_indices| {
+                    for &local_idx in local_indices {
+                        let offset = self.get_position(flock_start + local_idx as usize) - pos;
+                        let dist_sq = offset.length_squared();
+                        if dist_sq < f32::EPSILON || dist_sq >= flock.cohere_dist_sq { continue; }
+                        if len == count && dist_sq >= nearest[count - 1].0 { continue; }
+                        if let Some((heading, cos_half)) = widest_view {
+                            if heading.dot(offset) < cos_half * dist_sq.sqrt() { continue; }
+                        }
+                        
+                        let mut i = len.min(count - 1);
+                        while i > 0 && nearest[i - 1].0 > dist_sq {
+                            nearest[i] = nearest[i - 1];
+                            i -= 1;
+                        }
+                        nearest[i] = (dist_sq, local_idx);
+                        len = (len + 1).min(count);
+                    }
+                });
+                for &(_, local_idx) in &nearest[..len] {
+                    let neighbor_idx = flock_start + local_idx as usize;
+                    batch.push(self.get_position(neighbor_idx), self.get_velocity(neighbor_idx));
+                }
+            }
+        }
+        let sums = batch.finish();
+        let mut sep_sum = sums.sep_sum;
+        let mut align_sum = sums.align_sum;
+        let mut cohere_sum = sums.cohere_sum;
+        let mut counts = sums.counts;
+        let mut weights = sums.weights;
+        
+        let mut flee_sum = Vec3::ZERO;
+        let mut flee_weight = 0.0f32;
+        let mut chase_target = Vec3::ZERO;
+        let mut chase_dist_sq = f32::INFINITY;
+        let mut chase_weight = 0.0f32;
+        
+        // Boids of other flocks only count through an explicit relation
+        for relation in &self.relations[flock.relation_start as usize..flock.relation_end as usize] {
+            let other_flock = &self.flocks[relation.flock as usize];
+            if other_flock.start == other_flock.end { continue; }
+            
+            let other_start = other_flock.start as usize;
+            self.spatial_indices[relation.flock as usize].for_each_neighbor(pos, relation.distance, |local_indices| {
+                for &local_idx in local_indices {
+                    let other_pos = self.get_position(other_start + local_idx as usize);
+                    let diff = pos - other_pos;
+                    let dist_sq = diff.length_squared();
+                    
+                    if dist_sq < f32::EPSILON || dist_sq >= relation.distance_sq { continue; }
+                    
+                    match relation.kind {
+                        RelationKind::Ignore => {}
+                        RelationKind::Separate => {
+                            let dist = dist_sq.sqrt();
+                            let scale = separation_scale(&flock.sep_falloff, dist_sq, dist, 1.0 / relation.distance);
+                            sep_sum += diff * (scale * relation.weight);
+                            weights[0] += flock.sep_falloff.weight(dist / relation.distance);
+                            counts[0] += 1;
+                        }
+                        RelationKind::Flee => {
+                            flee_sum += diff * (1.0 / dist_sq);
+                            flee_weight = flee_weight.max(relation.weight);
+                        }
+                        RelationKind::Chase => {
+                            if dist_sq < chase_dist_sq {
+                                chase_dist_sq = dist_sq;
+                                chase_target = other_pos;
+                                chase_weight = relation.weight;
+                            }
+                        }
+                    }
+                }
+            });
+        }
+        
+        // Get boid properties (unsafe for speed)
+        let max_speed = unsafe { *self.max_speeds.get_unchecked(boid_idx) };
+        // Steering is limited to the velocity change allowed over `delta`, then turned back into an acceleration
+        let max_force = unsafe { *self.max_forces.get_unchecked(boid_idx) } * self.delta;
+        let sep_weight = unsafe { *self.separations.get_unchecked(boid_idx) };
+        let align_weight = unsafe { *self.alignments.get_unchecked(boid_idx) };
+        let cohere_weight = unsafe { *self.cohesions.get_unchecked(boid_idx) };
+        let target_weight = unsafe { *self.targetings.get_unchecked(boid_idx) };
+        let avoid_weight = unsafe { *self.avoidances.get_unchecked(boid_idx) };
+        let panic_weight = unsafe { *self.flees.get_unchecked(boid_idx) };
+        let wander_weight = unsafe { *self.wanders.get_unchecked(boid_idx) };
+        
+        let mut total_force = Vec3::ZERO;
+        
+        // Rules fade out along with the falloff of their neighbours, a single one at the edge of the radius barely counts
+        let [sep_fade, align_fade, cohere_fade] = weights.map(|weight| weight.min(1.0));
+        
+        // Fast force calculation with minimal branching
+        if counts[0] > 0 {
+            sep_sum *= 1.0 / counts[0] as f32;
+            let sep_len_sq = sep_sum.length_squared();
+            if sep_len_sq > 0.0 {
+                let desired = sep_sum * (max_speed / sep_len_sq.sqrt());
+                total_force += (desired - vel).clamp_length_max(max_force) * (sep_weight * sep_fade);
+            }
+        }
+        
+        if counts[1] > 0 {
+            align_sum *= 1.0 / counts[1] as f32;
+            let align_len_sq = align_sum.length_squared();
+            if align_len_sq > 0.0 {
+                let desired = align_sum * (max_speed / align_len_sq.sqrt());
+                total_force += (desired - vel).clamp_length_max(max_force) * (align_weight * align_fade);
+            }
+        }
+        
+        if counts[2] > 0 && weights[2] > 0.0 {
+            cohere_sum = cohere_sum * (1.0 / weights[2]) - pos;
+            let cohere_len_sq = cohere_sum.length_squared();
+            if cohere_len_sq > 0.0 {
+                let desired = cohere_sum * (max_speed / cohere_len_sq.sqrt());
+                total_force += (desired - vel).clamp_length_max(max_force) * (cohere_weight * cohere_fade);
+            }
+        }
+        
+        // Target following
+        if let Some(target) = flock.target {
+            let target_dir = target - pos;
+            let target_len_sq = target_dir.length_squared();
+            if target_len_sq > 0.0 {
+                let desired = target_dir * (max_speed / target_len_sq.sqrt());
+                total_force += (desired - vel).clamp_length_max(max_force) * target_weight;
+            }
+        }
+        
+        // Flee from related flocks
+        let flee_len_sq = flee_sum.length_squared();
+        if flee_len_sq > 0.0 {
+            let desired = flee_sum * (max_speed / flee_len_sq.sqrt());
+            total_force += (desired - vel).clamp_length_max(max_force) * flee_weight;
+        }
+        
+        // Chase the nearest boid of related flocks
+        if chase_dist_sq.is_finite() {
+            let chase_dir = chase_target - pos;
+            let desired = chase_dir * (max_speed / chase_dist_sq.sqrt());
+            total_force += (desired - vel).clamp_length_max(max_force) * chase_weight;
+        }
+        
+        // Steer back inside the flock's bounds, before leaving them if possible
+        if let Some(bounds) = flock.bounds {
+            let look_ahead = vel * (bounds.margin / max_speed.max(f32::EPSILON));
+            let here = bounds.containment(pos);
+            let ahead = bounds.containment(pos + look_ahead.clamp_length_max(bounds.margin));
+            if let Some((inward, urgency)) = [here, ahead].into_iter().flatten().max_by(|a, b| a.1.total_cmp(&b.1)) {
+                let desired = inward * max_speed;
+                total_force += (desired - vel).clamp_length_max(max_force) * (bounds.weight * urgency);
+            }
+        }
+        
+        // Steer away from obstacles near the boid or ahead of it
+        if avoid_weight != 0.0 && !self.obstacles.is_empty() {
+            let look_ahead = vel * (1.0 / max_speed.max(f32::EPSILON));
+            let heading = vel.normalize_or_zero();
+            let mut avoid_sum = Vec3::ZERO;
+            let mut urgency = 0.0f32;
+            for obstacle in &self.obstacles {
+                if !obstacle.is_in_reach(pos) { continue; }
+                
+                let here = obstacle.avoidance(pos);
+                let ahead = obstacle.avoidance(pos + look_ahead.clamp_length_max(1.0) * obstacle.margin);
+                // The more urgent of both probes wins
+                let nearest = [here, ahead].into_iter().flatten().max_by(|a, b| a.1.total_cmp(&b.1));
+                if let Some((away, obstacle_urgency)) = nearest {
+                    // Slide along the surface instead of bouncing back, pushing out harder the closer the boid gets
+                    let mut slide = heading - away * heading.dot(away).min(0.0);
+                    if slide.length_squared() < 1e-4 {
+                        // Head on, pick a side
+                        slide = match self.dimensions {
+                            Dimensions::D2 => vec3(-away.y, away.x, 0.0),
+                            Dimensions::D3 => away.any_orthonormal_vector(),
+                        };
+                    }
+                    avoid_sum += (slide.normalize() + away * obstacle_urgency).normalize_or_zero() * obstacle_urgency;
+                    urgency = urgency.max(obstacle_urgency);
+                }
+            }
+            
+            let avoid_len_sq = avoid_sum.length_squared();
+            if avoid_len_sq > 0.0 {
+                let desired = avoid_sum * (max_speed / avoid_len_sq.sqrt());
+                total_force += (desired - vel).clamp_length_max(max_force) * (avoid_weight * urgency);
+            }
+        }
+        
+        // Flee from predators, harder and faster the closer they are
+        let mut speed_boost = 0.0f32;
+        if panic_weight != 0.0 {
+            let mut panic_sum = Vec3::ZERO;
+            let mut panic = 0.0f32;
+            for predator in &self.predators {
+                let diff = pos - predator.position;
+                let dist_sq = diff.length_squared();
+                if dist_sq < f32::EPSILON || dist_sq >= predator.panic_radius * predator.panic_radius { continue; }
+                
+                let dist = dist_sq.sqrt();
+                let closeness = 1.0 - dist / predator.panic_radius;
+                panic_sum += diff * (closeness / dist);
+                panic = panic.max(closeness);
+                speed_boost = speed_boost.max(predator.speed_boost * closeness);
+            }
+            
+            let panic_len_sq = panic_sum.length_squared();
+            if panic_len_sq > 0.0 {
+                let desired = panic_sum * (max_speed * (1.0 + speed_boost) / panic_len_sq.sqrt());
+                total_force += (desired - vel).clamp_length_max(max_force) * (panic_weight * panic);
+            }
+        }
+        
+        // Wander towards a point on a circle ahead, moved around by noise. Every boid has its own spot in the noise
+        if wander_weight != 0.0 {
+            let id = unsafe { *self.boid_ids.get_unchecked(boid_idx) };
+            let spot = uvec3(hash(flock.seed, id), hash(flock.seed.wrapping_add(1), id), hash(flock.seed.wrapping_add(2), id));
+            let phase = spot.as_vec3() * (WANDER_SPREAD / u32::MAX as f32);
+            let sample = phase + Vec3::splat((flock.time * WANDER_RATE) as f32);
+            let mut jitter = vec3(
+                simplex3(sample, flock.seed),
+                simplex3(sample, flock.seed.wrapping_add(1)),
+                simplex3(sample, flock.seed.wrapping_add(2)),
+            );
+            if self.dimensions == Dimensions::D2 {
+                jitter.z = 0.0;
+            }
+            
+            let wander = vel.normalize_or_zero() + jitter * WANDER_RADIUS;
+            let wander_len_sq = wander.length_squared();
+            if wander_len_sq > 0.0 {
+                let desired = wander * (max_speed / wander_len_sq.sqrt());
+                total_force += (desired - vel).clamp_length_max(max_force) * wander_weight;
+            }
+        }
+        
+        (total_force * (1.0 / self.delta), speed_boost)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::Rng, BoidParams, FlockParams, FlockRelationInstance};
+    
+    // Crowded boids of three flocks, interleaved so no flock is passed in one piece
+    fn scattered_boids(count: u32) -> (Vec<BoidInstance>, Vec<FlockInstance>) {
+        let mut rng = Rng(0xbb67ae85);
+        let boids = (0..count).map(|i| {
+            let boid = BoidInstance::new(rng.vec3(120.0), rng.vec3(200.0), BoidParams::default(), i * 7 % 3);
+            BoidInstance { id: i, ..boid }
+        }).collect();
+        let flocks = (0..3).map(|_| FlockInstance::new(FlockParams::default(), None)).collect();
+        (boids, flocks)
+    }
+    
+    // Checks every loaded slot against the boid it was loaded from, returns the boids that got a slot
+    fn assert_slots_match(processor: &UltraBoidProcessor, boids: &[BoidInstance]) -> Vec<bool> {
+        let mut loaded = vec![false; boids.len()];
+        for (flock_idx, flock) in processor.flocks.iter().enumerate() {
+            for i in flock.start as usize..flock.end as usize {
+                let slot = processor.slots[i] as usize;
+                let boid = &boids[slot];
+                assert!(!loaded[slot], "boid {slot} got more than one slot");
+                loaded[slot] = true;
+                assert_eq!(boid.flock as usize, flock_idx, "slot {i} is in the range of another flock");
+                assert_eq!(processor.flock_indices[i], boid.flock);
+                assert_eq!(processor.boid_ids[i], boid.id);
+                assert_eq!(processor.get_position(i), boid.position);
+                assert_eq!(processor.get_velocity(i), boid.velocity);
+            }
+        }
+        loaded
+    }
+    
+    #[test]
+    fn slots_map_back_to_their_boids() {
+        let (mut boids, flocks) = scattered_boids(500);
+        let mut processor = UltraBoidProcessor::new(16, Dimensions::D3);
+        processor.load_flocks(&flocks);
+        processor.load_boids(&boids);
+        
+        let loaded = assert_slots_match(&processor, &boids);
+        assert!(loaded.iter().all(|&loaded| loaded), "every boid gets a slot");
+        let ranges: Vec<_> = processor.flocks.iter().map(|flock| flock.start..flock.end).collect();
+        assert!(ranges.windows(2).all(|pair| pair[0].end == pair[1].start), "flocks own contiguous slots");
+        
+        // A force written to a slot lands on the boid loaded into it
+        for i in 0..processor.count {
+            processor.forces_x[i] = processor.positions_x[i];
+        }
+        processor.store_forces(&mut boids);
+        assert!(boids.iter().all(|boid| boid.force.x == boid.position.x));
+    }
+    
+    #[test]
+    fn boids_past_the_limit_are_dropped_from_the_last_flocks() {
+        let (mut boids, flocks) = scattered_boids(500);
+        for boid in boids.iter_mut() {
+            boid.force = Vec3::ONE;
+        }
+        let mut processor = UltraBoidProcessor::new(16, Dimensions::D3);
+        processor.set_max_capacity(Some(300));
+        processor.process_boids(&mut boids, &flocks, 1.0 / 60.0);
+        assert_eq!(processor.dropped_count(), 200);
+        
+        let loaded = assert_slots_match(&processor, &boids);
+        // Earlier flocks keep all of their boids
+        let mut left = 300;
+        for flock in 0..3 {
+            let total = boids.iter().filter(|boid| boid.flock == flock).count();
+            let kept = boids.iter().zip(&loaded).filter(|(boid, &loaded)| boid.flock == flock && loaded).count();
+            assert_eq!(kept, total.min(left), "boids kept of flock {flock}");
+            left -= kept;
+        }
+        for (boid, &loaded) in boids.iter().zip(&loaded) {
+            if !loaded {
+                assert_eq!(boid.force, Vec3::ZERO, "dropped boids keep no force");
+            }
+        }
+        
+        processor.process_boids(&mut [], &flocks, 1.0 / 60.0);
+        assert_eq!(processor.dropped_count(), 0, "no boids are dropped once they are gone");
+    }
+    
+    #[test]
+    fn forces_dont_depend_on_boid_order() {
+        for dimensions in [Dimensions::D2, Dimensions::D3] {
+            let (mut boids, flocks) = scattered_boids(500);
+            if dimensions == Dimensions::D2 {
+                for boid in boids.iter_mut() {
+                    boid.position.z = 0.0;
+                    boid.velocity.z = 0.0;
+                }
+            }
+            let mut reversed: Vec<_> = boids.iter().rev().cloned().collect();
+            
+            let mut processor = UltraBoidProcessor::new(16, dimensions);
+            processor.process_boids(&mut boids, &flocks, 1.0 / 60.0);
+            processor.process_boids(&mut reversed, &flocks, 1.0 / 60.0);
+            for (boid, other) in boids.iter().zip(reversed.iter().rev()) {
+                let tolerance = 1e-3 * (1.0 + boid.force.abs().max_element());
+                assert!(
+                    (boid.force - other.force).abs().max_element() <= tolerance,
+                    "{dimensions:?} boid {} steers with {} or {} depending on order", boid.id, boid.force, other.force,
+                );
+            }
+        }
+    }
+    
+    // Force on a standing boid from a single neighbour at `distance`, with only one rule weighted
+    fn single_neighbor_force(params: BoidParams, falloff: Falloff, distance: f32) -> Vec3 {
+        let flock = FlockParams {
+            seperation_falloff: falloff,
+            alignment_falloff: falloff,
+            cohesion_falloff: falloff,
+            ..FlockParams::default()
+        };
+        let mut boids = vec![
+            BoidInstance::new(Vec3::ZERO, Vec3::ZERO, params, 0),
+            BoidInstance::new(Vec3::new(distance, 0.0, 0.0), Vec3::new(0.0, 100.0, 0.0), params, 0),
+        ];
+        let mut processor = UltraBoidProcessor::new(16, Dimensions::D3);
+        processor.process_boids(&mut boids, &[FlockInstance::new(flock, None)], 1.0 / 60.0);
+        boids[0].force
+    }
+    
+    #[test]
+    fn rules_fade_out_towards_the_edge_of_their_radius() {
+        let defaults = FlockParams::default();
+        let only = |seperation, alignment, cohesion| BoidParams { seperation, alignment, cohesion, ..BoidParams::default() };
+        for (rule, params, radius_sq) in [
+            ("separation", only(1.0, 0.0, 0.0), defaults.goal_seperation),
+            ("alignment", only(0.0, 1.0, 0.0), defaults.goal_alignment),
+            ("cohesion", only(0.0, 0.0, 1.0), defaults.goal_cohesion),
+        ] {
+            let radius = radius_sq.sqrt();
+            for t in [0.5, 0.999] {
+                let full = single_neighbor_force(params, Falloff::None, radius * t).length();
+                let faded = single_neighbor_force(params, Falloff::Linear, radius * t).length();
+                assert!(full > 0.0, "{rule} acts without falloff");
+                assert!((faded / full - (1.0 - t)).abs() < 1e-3, "{rule} at {t} of its radius steers with {faded} out of {full}");
+            }
+        }
+    }
+    
+    // Forces on a standing boid of the first flock and a boid of the second one off to its right, far past their perception radii
+    fn relation_forces(kind: RelationKind) -> (Vec3, Vec3) {
+        let mut boids = vec![
+            BoidInstance::new(Vec3::ZERO, Vec3::ZERO, BoidParams::default(), 0),
+            BoidInstance::new(Vec3::new(300.0, 0.0, 0.0), Vec3::ZERO, BoidParams::default(), 1),
+        ];
+        let mut flocks = vec![FlockInstance::new(FlockParams::default(), None); 2];
+        flocks[0].relations.push(FlockRelationInstance { flock: 1, kind, distance: 500.0 * 500.0, weight: 1.0 });
+        let mut processor = UltraBoidProcessor::new(16, Dimensions::D3);
+        processor.process_boids(&mut boids, &flocks, 1.0 / 60.0);
+        (boids[0].force, boids[1].force)
+    }
+    
+    #[test]
+    fn relations_act_on_the_other_flock_only_one_way() {
+        let (force, other) = relation_forces(RelationKind::Ignore);
+        assert_eq!(force, Vec3::ZERO, "ignored flocks are isolated");
+        assert_eq!(other, Vec3::ZERO);
+        
+        for (kind, direction) in [(RelationKind::Separate, -1.0), (RelationKind::Flee, -1.0), (RelationKind::Chase, 1.0)] {
+            let (force, other) = relation_forces(kind);
+            assert!(force.x * direction > 0.0, "{kind:?} steers with {force}");
+            assert!(force.y.abs() + force.z.abs() < 1e-3 * force.x.abs(), "{kind:?} steers with {force}");
+            assert_eq!(other, Vec3::ZERO, "{kind:?} doesn't act back on the other flock");
+        }
+    }
+    
+    // Force on and speed boost of a standing boid at `position`, with a predator at the origin
+    fn predator_response(position: Vec3) -> (Vec3, f32) {
+        let mut boids = vec![BoidInstance::new(position, Vec3::ZERO, BoidParams::default(), 0)];
+        let mut processor = UltraBoidProcessor::new(16, Dimensions::D3);
+        processor.load_predators([PredatorInstance::new(Vec3::ZERO, 100.0, 1.0)]);
+        processor.process_boids(&mut boids, &[FlockInstance::new(FlockParams::default(), None)], 1.0 / 60.0);
+        (boids[0].force, boids[0].speed_boost)
+    }
+    
+    #[test]
+    fn boids_flee_predators_within_their_panic_radius() {
+        let direction = Vec3::new(3.0, -1.0, 2.0).normalize();
+        let (far, far_boost) = predator_response(direction * 80.0);
+        let (near, near_boost) = predator_response(direction * 20.0);
+        for (force, boost, closeness) in [(far, far_boost, 0.2), (near, near_boost, 0.8)] {
+            assert!(force.normalize().dot(direction) > 0.999, "boid flees along {force}");
+            assert!((boost - closeness).abs() < 1e-3, "boost of {boost} at {closeness} closeness");
+        }
+        assert!(near.length() > far.length(), "closer boids panic harder");
+        
+        let (force, boost) = predator_response(direction * 120.0);
+        assert_eq!(force, Vec3::ZERO, "boids outside the panic radius stay calm");
+        assert_eq!(boost, 0.0);
+    }
+    
+    // Force on a boid at the origin heading along +X, among `neighbors` of its topological flock
+    fn topological_force(params: BoidParams, view_angle: f32, neighbors: &[(Vec3, Vec3)]) -> Vec3 {
+        let flock = FlockParams {
+            seperation_view_angle: view_angle,
+            alignment_view_angle: view_angle,
+            cohesion_view_angle: view_angle,
+            neighbor_mode: NeighborMode::Topological { count: 3, max_distance: 1e8 },
+            ..FlockParams::default()
+        };
+        let mut boids = vec![BoidInstance::new(Vec3::ZERO, Vec3::X * 0.01, params, 0)];
+        boids.extend(neighbors.iter().map(|&(position, velocity)| BoidInstance::new(position, velocity, params, 0)));
+        let mut processor = UltraBoidProcessor::new(16, Dimensions::D3);
+        processor.process_boids(&mut boids, &[FlockInstance::new(flock, None)], 1.0 / 60.0);
+        boids[0].force
+    }
+    
+    #[test]
+    fn topological_boids_react_to_their_nearest_visible_neighbors() {
+        let cohesion = BoidParams { seperation: 0.0, alignment: 0.0, cohesion: 1.0, ..BoidParams::default() };
+        let nearest = [Vec3::new(10.0, 5.0, 0.0), Vec3::new(12.0, -3.0, 0.0), Vec3::new(8.0, 0.0, 9.0)];
+        let mut neighbors: Vec<_> = nearest.iter().map(|&position| (position, Vec3::ZERO)).collect();
+        // Farther boids all around, and one right behind in the blind spot
+        neighbors.extend((0..12).map(|i| (Vec2::from_angle(i as f32 * TAU / 12.0).extend(0.0) * 40.0, Vec3::ZERO)));
+        neighbors.push((Vec3::new(-2.0, 0.0, 0.0), Vec3::ZERO));
+        
+        let force = topological_force(cohesion, TAU / 2.0, &neighbors);
+        let centroid = nearest.iter().sum::<Vec3>() / 3.0;
+        assert!(force.normalize().dot(centroid.normalize()) > 0.999, "coheres along {force} instead of towards {centroid}");
+        
+        // The boid behind is seen without a blind spot, and pulls the other way as one of the nearest
+        let force = topological_force(cohesion, TAU, &neighbors);
+        assert!(force.normalize().dot(centroid.normalize()) < 0.99, "blind spot still applies with {force}");
+    }
+    
+    #[test]
+    fn topological_forces_dont_depend_on_density() {
+        let params = BoidParams { seperation: 0.0, alignment: 1.0, cohesion: 1.0, ..BoidParams::default() };
+        let mut rng = Rng(0x510e527f);
+        let neighbors: Vec<_> = (0..40).map(|_| (rng.vec3(50.0), rng.vec3(200.0))).collect();
+        let dense = topological_force(params, TAU, &neighbors);
+        let spread: Vec<_> = neighbors.iter().map(|&(position, velocity)| (position * 4.0, velocity)).collect();
+        let sparse = topological_force(params, TAU, &spread);
+        assert!((dense - sparse).length() < 1e-3 * dense.length(), "{dense} when dense, {sparse} when spread out");
+    }
+    
+    // Forces of lone boids of a flock that has been wandering with `seed` for `time` seconds
+    fn wander_forces(seed: u32, time: f64) -> Vec<Vec3> {
+        let params = BoidParams { wander: 1.0, ..BoidParams::default() };
+        let mut boids: Vec<_> = (0..16).map(|i| {
+            let boid = BoidInstance::new(Vec3::new(i as f32 * 500.0, 0.0, 0.0), Vec3::new(0.0, 100.0, 0.0), params, 0);
+            BoidInstance { id: i, ..boid }
+        }).collect();
+        let flock = FlockInstance { seed, time, ..FlockInstance::new(FlockParams::default(), None) };
+        let mut processor = UltraBoidProcessor::new(16, Dimensions::D3);
+        processor.process_boids(&mut boids, &[flock], 1.0 / 60.0);
+        boids.iter().map(|boid| boid.force).collect()
+    }
+    
+    #[test]
+    fn wandering_is_deterministic_per_seed() {
+        let forces = wander_forces(7, 12.5);
+        assert!(forces.iter().all(|force| *force != Vec3::ZERO), "every boid wanders");
+        assert!(forces.windows(2).all(|pair| pair[0] != pair[1]), "boids wander on their own");
+        assert_eq!(wander_forces(7, 12.5), forces, "the same seed and time wander the same way");
+        for other in [wander_forces(8, 12.5), wander_forces(7, 14.0)] {
+            assert!(other.iter().zip(&forces).all(|(a, b)| a != b), "another seed or time wanders elsewhere");
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use glam::*;
+
+use crate::BoidInstance;
+
+/// Shape of a flock's bounds, in its local space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BoundsShape {
+    /// Box centered on the origin. A zero extent along an axis leaves it unbounded, like for a 2D rectangle.
+    Box { half_extents: Vec3 },
+    /// Sphere centered on the origin, a circle for 2D flocks.
+    Sphere { radius: f32 },
+}
+
+/// What happens to boids reaching the edge of their flock's bounds.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum BoundsMode {
+    /// Boids steer back inside once within the margin of the edge.
+    #[default]
+    Steer,
+    /// Boids leaving on one side come back in on the opposite side.
+    Wrap,
+    /// Boids are reflected off the edge.
+    Bounce,
+}
+
+/// Volume the boids of a flock are kept in, placed in the simulation by a transform.
+#[derive(Clone, Copy, Debug)]
+pub struct BoundsInstance {
+    pub shape: BoundsShape,
+    pub mode: BoundsMode,
+    /// Distance from the edge at which steering boids start turning back. Boids also look ahead by up to this distance.
+    pub margin: f32,
+    /// Weight of the steering force turning boids back inside.
+    pub weight: f32,
+    to_global: Affine3A,
+    to_local: Affine3A,
+}
+
+impl BoundsInstance {
+    pub fn new(shape: BoundsShape, mode: BoundsMode, transform: Affine3A, margin: f32, weight: f32) -> Self {
+        Self {
+            shape,
+            mode,
+            margin: margin.max(0.0),
+            weight,
+            to_global: transform,
+            to_local: transform.inverse(),
+        }
+    }
+
+    /// Direction pointing back inside at `point` and how urgent it is to go there,
+    /// from 0 at the margin to 1 at the edge and outside. `None` further inside than the margin.
+    pub fn containment(&self, point: Vec3) -> Option<(Vec3, f32)> {
+        let local = self.to_local.transform_point3(point);
+        // Nearest point on the edge, and whether `point` is past it
+        let (edge, outside) = match self.shape {
+            BoundsShape::Box { half_extents } => {
+                let clamped = local.clamp(-half_extents, half_extents);
+                if clamped != local {
+                    (clamped, true)
+                } else {
+                    let depth = (half_extents - local.abs()).to_array();
+                    let extents = half_extents.to_array();
+                    let axis = (0..3)
+                        .filter(|&axis| extents[axis] > 0.0)
+                        .min_by(|&a, &b| depth[a].total_cmp(&depth[b]))?;
+                    let mut edge = local.to_array();
+                    edge[axis] = extents[axis].copysign(edge[axis]);
+                    (Vec3::from_array(edge), false)
+                }
+            }
+            BoundsShape::Sphere { radius } => {
+                let outside = local.length_squared() > radius * radius;
+                (local.normalize_or(Vec3::Y) * radius, outside)
+            }
+        };
+
+        let offset = self.to_global.transform_point3(edge) - point;
+        let distance = offset.length();
+        if !outside && distance >= self.margin {
+            return None;
+        }
+        // Right on the edge the offset has no direction, head for the center instead
+        let center: Vec3 = self.to_global.translation.into();
+        let inward = if distance > f32::EPSILON {
+            if outside { offset / distance } else { -offset / distance }
+        } else {
+            (center - point).normalize_or_zero()
+        };
+        let urgency = if outside { 1.0 } else { 1.0 - distance / self.margin };
+        Some((inward, urgency))
+    }
+
+    /// Wraps or bounces a boid that left the bounds, depending on [`Self::mode`]. Steering bounds leave it alone.
+    pub fn confine(&self, boid: &mut BoidInstance) {
+        let mut position = self.to_local.transform_point3(boid.position);
+        let mut velocity = self.to_local.transform_vector3(boid.velocity);
+
+        match (self.shape, self.mode) {
+            (_, BoundsMode::Steer) => return,
+            (BoundsShape::Box { half_extents }, BoundsMode::Wrap) => {
+                let mut wrapped = position.to_array();
+                for (p, h) in wrapped.iter_mut().zip(half_extents.to_array()) {
+                    if h > 0.0 && p.abs() > h {
+                        *p = (*p + h).rem_euclid(2.0 * h) - h;
+                    }
+                }
+                let wrapped = Vec3::from_array(wrapped);
+                if wrapped == position { return; }
+                position = wrapped;
+            }
+            (BoundsShape::Box { half_extents }, BoundsMode::Bounce) => {
+                let mut reflected = position.to_array();
+                let mut velocities = velocity.to_array();
+                for ((p, v), h) in reflected.iter_mut().zip(velocities.iter_mut()).zip(half_extents.to_array()) {
+                    if h <= 0.0 || p.abs() <= h { continue; }
+                    let side = p.signum();
+                    *p = (2.0 * h * side - *p).clamp(-h, h);
+                    *v = -v.abs() * side;
+                }
+                let reflected = Vec3::from_array(reflected);
+                if reflected == position { return; }
+                position = reflected;
+                velocity = Vec3::from_array(velocities);
+            }
+            (BoundsShape::Sphere { radius }, mode) => {
+                let distance = position.length();
+                if distance <= radius || distance <= f32::EPSILON { return; }
+                let normal = position / distance;
+                let overshoot = (distance - radius).min(radius);
+                if mode == BoundsMode::Wrap {
+                    // Come back in through the opposite point of the sphere
+                    position = -normal * (radius - overshoot);
+                } else {
+                    position = normal * (radius - overshoot);
+                    let outward = velocity.dot(normal);
+                    if outward > 0.0 {
+                        velocity -= normal * (2.0 * outward);
+                    }
+                }
+            }
+        }
+
+        boid.position = self.to_global.transform_point3(position);
+        boid.velocity = self.to_global.transform_vector3(velocity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{confine_boids, integrate_boids, BoidAlgorithm, BoidParams, Dimensions, FlockInstance, FlockParams, UltraBoidProcessor};
+    use crate::testing::Rng;
+
+    // How far `point` is outside of `shape`, placed by `to_local`
+    fn overshoot(shape: BoundsShape, to_local: Affine3A, point: Vec3) -> f32 {
+        let local = to_local.transform_point3(point);
+        match shape {
+            BoundsShape::Box { half_extents } => {
+                let unbounded = half_extents.cmple(Vec3::ZERO);
+                let outside = Vec3::select(unbounded, Vec3::ZERO, local.abs() - half_extents);
+                outside.max_element().max(0.0)
+            }
+            BoundsShape::Sphere { radius } => (local.length() - radius).max(0.0),
+        }
+    }
+
+    // Largest overshoot of boids flying off in every direction from the center of the bounds
+    fn max_overshoot(shape: BoundsShape, mode: BoundsMode, dimensions: Dimensions) -> f32 {
+        let transform = Affine3A::from_rotation_translation(Quat::from_rotation_z(0.5), Vec3::new(50.0, 20.0, 0.0));
+        let bounds = BoundsInstance::new(shape, mode, transform, 40.0, 2.0);
+        let mut flock = FlockInstance::new(FlockParams::default(), None);
+        flock.bounds = Some(bounds);
+        let flocks = [flock];
+
+        let mut rng = Rng(0x6a09e667);
+        let center = transform.translation.into();
+        let mut boids: Vec<_> = (0..100).map(|_| {
+            let mut velocity = rng.vec3(1.0);
+            if dimensions == Dimensions::D2 {
+                velocity.z = 0.0;
+            }
+            BoidInstance::new(center, velocity.normalize_or(Vec3::X) * 240.0, BoidParams::default(), 0)
+        }).collect();
+
+        let delta = 1.0 / 60.0;
+        let mut processor = UltraBoidProcessor::new(16, dimensions);
+        let mut max = 0.0f32;
+        for _ in 0..600 {
+            processor.process_boids(&mut boids, &flocks, delta);
+            integrate_boids(&mut boids, delta);
+            confine_boids(&mut boids, &bounds);
+            for boid in &boids {
+                max = max.max(overshoot(shape, transform.inverse(), boid.position));
+            }
+        }
+        max
+    }
+
+    const SHAPES: [(BoundsShape, Dimensions); 4] = [
+        (BoundsShape::Box { half_extents: Vec3::new(100.0, 100.0, 0.0) }, Dimensions::D2),
+        (BoundsShape::Sphere { radius: 100.0 }, Dimensions::D2),
+        (BoundsShape::Box { half_extents: Vec3::new(100.0, 60.0, 80.0) }, Dimensions::D3),
+        (BoundsShape::Sphere { radius: 100.0 }, Dimensions::D3),
+    ];
+
+    #[test]
+    fn wrapping_and_bouncing_keep_boids_inside() {
+        for (shape, dimensions) in SHAPES {
+            for mode in [BoundsMode::Wrap, BoundsMode::Bounce] {
+                let max = max_overshoot(shape, mode, dimensions);
+                assert!(max < 1e-3, "{mode:?} {shape:?} let boids {max} outside");
+            }
+        }
+    }
+
+    #[test]
+    fn steering_turns_boids_back_within_the_margin() {
+        for (shape, dimensions) in SHAPES {
+            let max = max_overshoot(shape, BoundsMode::Steer, dimensions);
+            assert!(max < 40.0, "Steer {shape:?} let boids {max} outside");
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use glam::*;
+
+use crate::{BoidInstance, BoundsInstance};
+
+/// Applies a steering force (units per second squared) over `delta` seconds to a velocity,
+/// limiting the result to `max_speed` (units per second).
+#[inline(always)]
+pub fn integrate_velocity(velocity: Vec3, force: Vec3, max_speed: f32, delta: f32) -> Vec3 {
+    (velocity + force * delta).clamp_length_max(max_speed)
+}
+
+/// Moves every boid by its force over `delta` seconds, the same way the engine nodes do.
+/// Boids go up to `max_speed`, raised by their current speed boost.
+///
+/// Meant for driving the simulation without an engine, right after [`crate::BoidAlgorithm::process_boids`]
+/// with the same `delta`.
+pub fn integrate_boids(boids: &mut [BoidInstance], delta: f32) {
+    for boid in boids.iter_mut() {
+        let max_speed = boid.properties.max_speed * (1.0 + boid.speed_boost);
+        boid.velocity = integrate_velocity(boid.velocity, boid.force, max_speed, delta);
+        boid.position += boid.velocity * delta;
+    }
+}
+
+/// Wraps or bounces every boid that left `bounds`, call it right after [`integrate_boids`].
+pub fn confine_boids(boids: &mut [BoidInstance], bounds: &BoundsInstance) {
+    for boid in boids.iter_mut() {
+        bounds.confine(boid);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BoidAlgorithm, BoidParams, Dimensions, FlockInstance, FlockParams, UltraBoidProcessor};
+
+    const FRAME_RATES: [f32; 4] = [30.0, 60.0, 144.0, 240.0];
+
+    // Runs `seconds` of simulation at `frame_rate` ticks per second, stepping `boids` with `tick`
+    fn simulate(boids: &[BoidInstance], frame_rate: f32, seconds: f32, mut tick: impl FnMut(&mut [BoidInstance], f32)) -> Vec<BoidInstance> {
+        let mut boids = boids.to_vec();
+        for _ in 0..(seconds * frame_rate).round() as u32 {
+            tick(&mut boids, 1.0 / frame_rate);
+            integrate_boids(&mut boids, 1.0 / frame_rate);
+        }
+        boids
+    }
+
+    // Asserts every frame rate ends up within `tolerance` of the first, relative to the distance travelled
+    fn assert_frame_rate_independent(start: &[BoidInstance], results: &[Vec<BoidInstance>], tolerance: f32) {
+        for (frame_rate, boids) in FRAME_RATES.iter().zip(results) {
+            for ((boid, expected), start) in boids.iter().zip(&results[0]).zip(start) {
+                let travelled = expected.position.distance(start.position);
+                let off = boid.position.distance(expected.position);
+                assert!(off <= tolerance * travelled, "{frame_rate} fps ends {off} away after travelling {travelled}");
+            }
+        }
+    }
+
+    #[test]
+    fn forces_integrate_per_second() {
+        let properties = BoidParams { max_speed: 100.0, ..Default::default() };
+        let start: Vec<_> = [Vec3::X, Vec3::new(-1.0, 2.0, 0.5)]
+            .map(|direction| BoidInstance { force: direction * 150.0, ..BoidInstance::new(Vec3::ZERO, Vec3::ZERO, properties, 0) })
+            .into();
+        let results: Vec<_> = FRAME_RATES.iter().map(|&frame_rate| simulate(&start, frame_rate, 2.0, |_, _| {})).collect();
+        for (frame_rate, boids) in FRAME_RATES.iter().zip(&results) {
+            for boid in boids {
+                assert!((boid.velocity.length() - 100.0).abs() < 1e-3, "{frame_rate} fps reaches {} instead of max_speed", boid.velocity);
+            }
+        }
+        assert_frame_rate_independent(&start, &results, 0.02);
+    }
+
+    #[test]
+    fn steering_is_frame_rate_independent() {
+        let properties = BoidParams { max_force: 400.0, ..Default::default() };
+        // Boids too far apart to see each other, so only the target steers them
+        let start: Vec<_> = (0..8)
+            .map(|i| BoidInstance::new(Vec3::new(i as f32 * 200.0, 0.0, 0.0), Vec3::new(0.0, 100.0, 0.0), properties, 0))
+            .collect();
+        let flocks = [FlockInstance::new(FlockParams::default(), Some(Vec3::new(400.0, -300.0, 0.0)))];
+        let results: Vec<_> = FRAME_RATES.iter().map(|&frame_rate| {
+            let mut processor = UltraBoidProcessor::new(16, Dimensions::D2);
+            simulate(&start, frame_rate, 2.0, |boids, delta| processor.process_boids(boids, &flocks, delta))
+        }).collect();
+        assert_frame_rate_independent(&start, &results, 0.05);
+    }
+
+    #[test]
+    fn speed_boosts_raise_max_speed_then_fade() {
+        let properties = BoidParams { max_speed: 100.0, ..Default::default() };
+        let mut boids = vec![BoidInstance { force: Vec3::X * 1e4, speed_boost: 0.5, ..BoidInstance::new(Vec3::ZERO, Vec3::ZERO, properties, 0) }];
+        integrate_boids(&mut boids, 0.1);
+        assert!((boids[0].velocity.length() - 150.0).abs() < 1e-3, "boosted boid reaches {}", boids[0].velocity);
+
+        // Without predators around, the boost fades by one `max_speed` per second
+        let flocks = [FlockInstance::new(FlockParams::default(), None)];
+        let mut processor = UltraBoidProcessor::new(16, Dimensions::D3);
+        let mut tick = |boids: &mut [BoidInstance], delta| processor.process_boids(boids, &flocks, delta);
+        let boids = simulate(&boids, 60.0, 0.25, &mut tick);
+        assert!((boids[0].speed_boost - 0.25).abs() < 1e-3, "boost fades to {}", boids[0].speed_boost);
+        assert!((boids[0].velocity.length() - 125.0).abs() < 0.1, "fading boost slows to {}", boids[0].velocity);
+        let boids = simulate(&boids, 60.0, 0.5, &mut tick);
+        assert_eq!(boids[0].speed_boost, 0.0);
+        assert!((boids[0].velocity.length() - 100.0).abs() < 1e-3, "boid slows back to {}", boids[0].velocity);
+    }
+}
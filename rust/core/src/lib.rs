@@ -0,0 +1,24 @@
+//! Engine-independent boids simulation.
+//!
+//! Everything in here is plain Rust (no Godot types), so it can be driven from headless tools,
+//! servers or tests. The GDExtension in the parent crate is a thin layer converting its nodes and
+//! resources into these types every tick.
+
+mod algorithms;
+mod bounds;
+mod integration;
+pub mod noise;
+mod obstacle;
+mod predator;
+mod properties;
+pub mod spatial;
+#[cfg(test)]
+mod testing;
+
+pub use algorithms::*;
+pub use bounds::*;
+pub use integration::*;
+pub use obstacle::*;
+pub use predator::*;
+pub use properties::*;
+pub use spatial::{SpatialIndex, SpatialIndexKind, SpatialStructure};
@@ -0,0 +1,85 @@
+use glam::*;
+
+// Skews regular space onto the simplex grid and back
+const SKEW: f32 = 1.0 / 3.0;
+const UNSKEW: f32 = 1.0 / 6.0;
+
+// Edge midpoints of a cube, the usual simplex noise gradients
+const GRADIENTS: [Vec3; 12] = [
+    vec3(1.0, 1.0, 0.0), vec3(-1.0, 1.0, 0.0), vec3(1.0, -1.0, 0.0), vec3(-1.0, -1.0, 0.0),
+    vec3(1.0, 0.0, 1.0), vec3(-1.0, 0.0, 1.0), vec3(1.0, 0.0, -1.0), vec3(-1.0, 0.0, -1.0),
+    vec3(0.0, 1.0, 1.0), vec3(0.0, -1.0, 1.0), vec3(0.0, 1.0, -1.0), vec3(0.0, -1.0, -1.0),
+];
+
+/// Well mixed 32 bit hash of `value`, a different one for every `seed`.
+#[inline(always)]
+pub fn hash(seed: u32, value: u32) -> u32 {
+    let mut h = seed.wrapping_mul(0x9e3779b9) ^ value;
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846ca68b);
+    h ^= h >> 16;
+    h
+}
+
+#[inline(always)]
+fn hash_cell(seed: u32, cell: IVec3) -> u32 {
+    hash(hash(hash(seed, cell.x as u32), cell.y as u32), cell.z as u32)
+}
+
+/// 3D simplex noise, smooth and within `[-1, 1]`. Every `seed` gives a different field.
+pub fn simplex3(point: Vec3, seed: u32) -> f32 {
+    let skewed = (point + Vec3::splat(point.element_sum() * SKEW)).floor();
+    let cell = skewed.as_ivec3();
+    let offset = point - (skewed - Vec3::splat(skewed.element_sum() * UNSKEW));
+
+    // The skewed cube is made of six simplices, walk the one `point` is in along its largest offsets first
+    let (second, third) = if offset.x >= offset.y {
+        if offset.y >= offset.z {
+            (IVec3::X, ivec3(1, 1, 0))
+        } else if offset.x >= offset.z {
+            (IVec3::X, ivec3(1, 0, 1))
+        } else {
+            (IVec3::Z, ivec3(1, 0, 1))
+        }
+    } else if offset.y < offset.z {
+        (IVec3::Z, ivec3(0, 1, 1))
+    } else if offset.x < offset.z {
+        (IVec3::Y, ivec3(0, 1, 1))
+    } else {
+        (IVec3::Y, ivec3(1, 1, 0))
+    };
+
+    let mut sum = 0.0;
+    for (i, corner) in [IVec3::ZERO, second, third, IVec3::ONE].into_iter().enumerate() {
+        let d = offset - corner.as_vec3() + Vec3::splat(i as f32 * UNSKEW);
+        let falloff = 0.6 - d.length_squared();
+        if falloff <= 0.0 { continue; }
+        let gradient = GRADIENTS[(hash_cell(seed, cell + corner) % 12) as usize];
+        sum += falloff * falloff * falloff * falloff * gradient.dot(d);
+    }
+    // Peaks land just short of 1, clamped so rounding can't overshoot
+    (32.0 * sum).clamp(-1.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Rng;
+
+    #[test]
+    fn simplex_stays_within_unit_range() {
+        let mut rng = Rng(0x6a09e667);
+        let (mut min, mut max) = (f32::INFINITY, f32::NEG_INFINITY);
+        for seed in 0..8 {
+            for _ in 0..100_000 {
+                let value = simplex3(rng.vec3(50.0), seed);
+                min = min.min(value);
+                max = max.max(value);
+            }
+        }
+        assert!(min >= -1.0 && max <= 1.0, "noise reaches [{min}, {max}]");
+        assert!(min < -0.8 && max > 0.8, "noise only spans [{min}, {max}]");
+    }
+}
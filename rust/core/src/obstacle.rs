@@ -0,0 +1,265 @@
+use std::sync::Arc;
+
+use glam::*;
+
+/// Shape of an obstacle, in its local space.
+///
+/// 2D obstacles live on the xy plane, their shapes are extruded along z.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ObstacleShape {
+    /// Single point, only avoided through the obstacle's margin.
+    Point,
+    Sphere { radius: f32 },
+    /// Box centered on the origin. A zero extent along an axis makes it flat, like a 2D rectangle.
+    Box { half_extents: Vec3 },
+    /// Capsule centered on the origin, along y.
+    Capsule { radius: f32, half_height: f32 },
+    /// Closed polygon on the xy plane, extruded along z. Shared, so rebuilding the obstacle doesn't copy the points.
+    Polygon { points: Arc<[Vec2]> },
+}
+
+impl ObstacleShape {
+    // Closest point on the surface and whether `point` is inside, both in local space
+    fn closest_point(&self, point: Vec3) -> Option<(Vec3, bool)> {
+        match self {
+            Self::Point => Some((Vec3::ZERO, false)),
+            Self::Sphere { radius } => {
+                let inside = point.length_squared() < radius * radius;
+                Some((point.normalize_or(Vec3::Y) * *radius, inside))
+            }
+            Self::Box { half_extents } => {
+                let clamped = point.clamp(-*half_extents, *half_extents);
+                if clamped != point {
+                    return Some((clamped, false));
+                }
+                // Inside, push out through the nearest face. Flat axes have no face to leave through
+                let depth = (*half_extents - point.abs()).to_array();
+                let extents = half_extents.to_array();
+                let axis = (0..3)
+                    .filter(|&axis| extents[axis] > 0.0)
+                    .min_by(|&a, &b| depth[a].total_cmp(&depth[b]))?;
+                let mut closest = point.to_array();
+                closest[axis] = extents[axis].copysign(closest[axis]);
+                Some((Vec3::from_array(closest), true))
+            }
+            Self::Capsule { radius, half_height } => {
+                let segment = vec3(0.0, point.y.clamp(-half_height, *half_height), 0.0);
+                let offset = point - segment;
+                let inside = offset.length_squared() < radius * radius;
+                Some((segment + offset.normalize_or(Vec3::X) * *radius, inside))
+            }
+            Self::Polygon { points } => {
+                let p = point.xy();
+                let mut closest = *points.first()?;
+                let mut closest_dist_sq = closest.distance_squared(p);
+                let mut inside = false;
+                for (i, &a) in points.iter().enumerate() {
+                    let b = points[(i + 1) % points.len()];
+                    let edge = b - a;
+                    let t = ((p - a).dot(edge) / edge.length_squared().max(f32::EPSILON)).clamp(0.0, 1.0);
+                    let on_edge = a + edge * t;
+                    let dist_sq = on_edge.distance_squared(p);
+                    if dist_sq < closest_dist_sq {
+                        closest = on_edge;
+                        closest_dist_sq = dist_sq;
+                    }
+                    // Even-odd crossings of a ray towards +x
+                    if (a.y > p.y) != (b.y > p.y) && p.x < a.x + (p.y - a.y) / (b.y - a.y) * edge.x {
+                        inside = !inside;
+                    }
+                }
+                Some((closest.extend(point.z), inside))
+            }
+        }
+    }
+
+    // Radius of a sphere around the origin enclosing the shape
+    fn bounding_radius(&self) -> f32 {
+        match self {
+            Self::Point => 0.0,
+            Self::Sphere { radius } => *radius,
+            Self::Box { half_extents } => half_extents.length(),
+            Self::Capsule { radius, half_height } => radius + half_height,
+            Self::Polygon { points } => points.iter().map(|p| p.length()).fold(0.0, f32::max),
+        }
+    }
+}
+
+/// Obstacle boids steer around, placed in the simulation by a transform.
+#[derive(Clone, Debug)]
+pub struct ObstacleInstance {
+    pub shape: ObstacleShape,
+    /// Distance from the surface at which boids start steering away.
+    /// Boids look ahead by up to this distance along their velocity, the faster they go the further.
+    pub margin: f32,
+    to_global: Affine3A,
+    to_local: Affine3A,
+    // Enclosing sphere in global space, including the margin
+    center: Vec3,
+    reach: f32,
+}
+
+impl ObstacleInstance {
+    pub fn new(shape: ObstacleShape, transform: Affine3A, margin: f32) -> Self {
+        let scale = transform.matrix3.x_axis.length()
+            .max(transform.matrix3.y_axis.length())
+            .max(transform.matrix3.z_axis.length());
+        let reach = shape.bounding_radius() * scale + margin.max(0.0);
+        Self {
+            shape,
+            margin: margin.max(0.0),
+            to_global: transform,
+            to_local: transform.inverse(),
+            center: transform.translation.into(),
+            reach,
+        }
+    }
+
+    /// Whether a boid at `position` looking ahead by `margin` could get close enough to react.
+    #[inline(always)]
+    pub fn is_in_reach(&self, position: Vec3) -> bool {
+        let reach = self.reach + self.margin;
+        position.distance_squared(self.center) < reach * reach
+    }
+
+    /// Direction pointing away from the obstacle at `point` and how urgent it is to go there,
+    /// from 0 at the margin to 1 at the surface and inside. `None` past the margin.
+    pub fn avoidance(&self, point: Vec3) -> Option<(Vec3, f32)> {
+        let (closest, inside) = self.shape.closest_point(self.to_local.transform_point3(point))?;
+        let closest = self.to_global.transform_point3(closest);
+        let offset = point - closest;
+        let distance = offset.length();
+        if !inside && distance >= self.margin {
+            return None;
+        }
+
+        // Right on the surface the offset has no direction, move away from the center instead
+        let away = if distance > f32::EPSILON {
+            if inside { -offset / distance } else { offset / distance }
+        } else {
+            (point - self.center).normalize_or(Vec3::Y)
+        };
+        let urgency = if inside { 1.0 } else { 1.0 - distance / self.margin };
+        Some((away, urgency))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BoidAlgorithm, BoidInstance, BoidParams, Dimensions, FlockInstance, FlockParams, UltraBoidProcessor};
+
+    fn assert_close(actual: Vec3, expected: Vec3) {
+        assert!(actual.distance(expected) < 1e-4, "{actual} isn't {expected}");
+    }
+
+    #[test]
+    fn closest_point_on_a_box() {
+        let shape = ObstacleShape::Box { half_extents: vec3(10.0, 5.0, 2.0) };
+        let (closest, inside) = shape.closest_point(vec3(15.0, 1.0, -4.0)).unwrap();
+        assert_close(closest, vec3(10.0, 1.0, -2.0));
+        assert!(!inside);
+
+        // Inside, out through the nearest face
+        let (closest, inside) = shape.closest_point(vec3(3.0, -4.0, 0.5)).unwrap();
+        assert_close(closest, vec3(3.0, -5.0, 0.5));
+        assert!(inside);
+
+        // Flat boxes are left through their sides only
+        let flat = ObstacleShape::Box { half_extents: vec3(10.0, 5.0, 0.0) };
+        let (closest, inside) = flat.closest_point(vec3(9.0, 1.0, 0.0)).unwrap();
+        assert_close(closest, vec3(10.0, 1.0, 0.0));
+        assert!(inside);
+    }
+
+    #[test]
+    fn closest_point_on_a_capsule() {
+        let shape = ObstacleShape::Capsule { radius: 2.0, half_height: 5.0 };
+        // Beside the segment, and past its caps
+        let (closest, inside) = shape.closest_point(vec3(4.0, 3.0, 0.0)).unwrap();
+        assert_close(closest, vec3(2.0, 3.0, 0.0));
+        assert!(!inside);
+        let (closest, inside) = shape.closest_point(vec3(0.0, 9.0, 0.0)).unwrap();
+        assert_close(closest, vec3(0.0, 7.0, 0.0));
+        assert!(!inside);
+        // Inside, out through the nearest point of the side or the cap
+        let (closest, inside) = shape.closest_point(vec3(1.0, 2.0, 0.0)).unwrap();
+        assert_close(closest, vec3(2.0, 2.0, 0.0));
+        assert!(inside);
+        let (closest, inside) = shape.closest_point(vec3(0.0, -6.0, 0.0)).unwrap();
+        assert_close(closest, vec3(0.0, -7.0, 0.0));
+        assert!(inside);
+    }
+
+    #[test]
+    fn polygons_are_inside_by_even_odd_crossings() {
+        // U shape, opening upwards
+        let points: Arc<[Vec2]> = [
+            vec2(-10.0, -10.0), vec2(10.0, -10.0), vec2(10.0, 10.0), vec2(5.0, 10.0),
+            vec2(5.0, 0.0), vec2(-5.0, 0.0), vec2(-5.0, 10.0), vec2(-10.0, 10.0),
+        ].into();
+        let shape = ObstacleShape::Polygon { points };
+        for (point, expected) in [
+            (vec2(0.0, -5.0), true),
+            (vec2(-7.0, 5.0), true),
+            (vec2(7.0, 5.0), true),
+            (vec2(0.0, 5.0), false),
+            (vec2(20.0, 0.0), false),
+            (vec2(0.0, -15.0), false),
+        ] {
+            let (_, inside) = shape.closest_point(point.extend(3.0)).unwrap();
+            assert_eq!(inside, expected, "{point} inside");
+        }
+
+        // The closest point is on the nearest edge, at the same depth
+        let (closest, _) = shape.closest_point(vec3(0.0, 4.0, 3.0)).unwrap();
+        assert_close(closest, vec3(0.0, 0.0, 3.0));
+        assert_eq!(ObstacleShape::Polygon { points: Arc::new([]) }.closest_point(Vec3::ZERO), None);
+    }
+
+    #[test]
+    fn avoidance_grows_towards_the_surface() {
+        let transform = Affine3A::from_scale_rotation_translation(Vec3::splat(2.0), Quat::from_rotation_z(0.3), vec3(100.0, 0.0, 0.0));
+        let obstacle = ObstacleInstance::new(ObstacleShape::Sphere { radius: 10.0 }, transform, 20.0);
+        // Scaled to a radius of 20
+        assert_eq!(obstacle.avoidance(vec3(141.0, 0.0, 0.0)), None);
+        let (away, far) = obstacle.avoidance(vec3(135.0, 0.0, 0.0)).unwrap();
+        let (_, near) = obstacle.avoidance(vec3(125.0, 0.0, 0.0)).unwrap();
+        let (inward, inside) = obstacle.avoidance(vec3(110.0, 0.0, 0.0)).unwrap();
+        assert_close(away, Vec3::X);
+        assert_close(inward, Vec3::X);
+        assert!((far - 0.25).abs() < 1e-4 && (near - 0.75).abs() < 1e-4, "urgency {far} {near}");
+        assert_eq!(inside, 1.0);
+    }
+
+    // Avoidance force on a boid at `position` flying along +x at full speed towards a sphere at the origin
+    fn avoidance_force(position: Vec3, dimensions: Dimensions) -> Vec3 {
+        let properties = BoidParams::default();
+        let mut boids = [BoidInstance::new(position, Vec3::X * properties.max_speed, properties, 0)];
+        let flocks = [FlockInstance::new(FlockParams::default(), None)];
+        let mut processor = UltraBoidProcessor::new(16, dimensions);
+        processor.load_obstacles([ObstacleInstance::new(ObstacleShape::Sphere { radius: 50.0 }, Affine3A::IDENTITY, 20.0)]);
+        processor.process_boids(&mut boids, &flocks, 1.0 / 60.0);
+        boids[0].force
+    }
+
+    #[test]
+    fn boids_look_ahead_by_the_margin() {
+        // 1.5 margins from the surface, only the look-ahead reaches into the margin
+        let force = avoidance_force(vec3(-80.0, 1.0, 0.0), Dimensions::D2);
+        assert!(force.length() > 0.0, "no avoidance ahead of the margin");
+        assert_eq!(avoidance_force(vec3(-95.0, 1.0, 0.0), Dimensions::D2), Vec3::ZERO);
+    }
+
+    #[test]
+    fn boids_heading_straight_at_an_obstacle_turn_aside() {
+        for dimensions in [Dimensions::D2, Dimensions::D3] {
+            let force = avoidance_force(vec3(-60.0, 0.0, 0.0), dimensions);
+            let sideways = force.reject_from(Vec3::X);
+            assert!(sideways.length() > 1.0, "{dimensions:?} head on steers {force}");
+            if dimensions == Dimensions::D2 {
+                assert_eq!(force.z, 0.0);
+            }
+        }
+    }
+}
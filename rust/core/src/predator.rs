@@ -0,0 +1,22 @@
+use glam::*;
+
+/// Threat boids of every flock flee from while within its panic radius.
+#[derive(Clone, Copy, Debug)]
+pub struct PredatorInstance {
+    pub position: Vec3,
+    /// Distance within which boids flee, the closer they are the harder.
+    pub panic_radius: f32,
+    /// Extra speed, relative to `max_speed`, boids may reach while fleeing right next to the predator.
+    pub speed_boost: f32,
+}
+
+impl PredatorInstance {
+    #[inline(always)]
+    pub fn new(position: Vec3, panic_radius: f32, speed_boost: f32) -> Self {
+        Self {
+            position,
+            panic_radius: panic_radius.max(0.0),
+            speed_boost: speed_boost.max(0.0),
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use std::f32::consts::TAU;
+
+/// Per-boid steering properties.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoidParams {
+    /// Maximum speed, in units per second.
+    pub max_speed: f32,
+    /// Maximum steering acceleration, in units per second squared.
+    pub max_force: f32,
+    pub alignment: f32,
+    pub cohesion: f32,
+    pub seperation: f32,
+    pub targeting: f32,
+    /// Weight of steering around obstacles.
+    pub avoidance: f32,
+    /// Weight of fleeing from predators.
+    pub flee: f32,
+    /// Weight of wandering around on a whim, none by default.
+    pub wander: f32,
+}
+
+impl Default for BoidParams {
+    fn default() -> Self {
+        Self {
+            max_speed: 240.0,
+            max_force: 3600.0,
+            alignment: 1.5,
+            cohesion: 1.0,
+            seperation: 1.2,
+            targeting: 0.8,
+            avoidance: 2.0,
+            flee: 3.0,
+            wander: 0.0,
+        }
+    }
+}
+
+/// Per-flock perception properties.
+///
+/// Distances are squared, and used as is for both 2D and 3D.
+/// View angles are the full width of the cone a boid sees neighbours in, centered on its velocity, in radians.
+/// A full turn leaves no blind spot.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FlockParams {
+    /// Distance (squared) to apply seperation force between boids in a flock.
+    pub goal_seperation: f32,
+    /// Distance (squared) to apply alignment force between boids in a flock.
+    pub goal_alignment: f32,
+    /// Distance (squared) to apply cohesion force between boids in a flock.
+    pub goal_cohesion: f32,
+    /// View angle of seperation, boids only keep their distance from neighbours in it.
+    pub seperation_view_angle: f32,
+    /// View angle of alignment, boids only align with neighbours in it.
+    pub alignment_view_angle: f32,
+    /// View angle of cohesion, boids only cohere with neighbours in it.
+    pub cohesion_view_angle: f32,
+    /// How boids pick the neighbours the rules apply to.
+    pub neighbor_mode: NeighborMode,
+    /// How much neighbours count towards seperation by their distance.
+    pub seperation_falloff: Falloff,
+    /// How much neighbours count towards alignment by their distance.
+    pub alignment_falloff: Falloff,
+    /// How much neighbours count towards cohesion by their distance.
+    pub cohesion_falloff: Falloff,
+}
+
+impl Default for FlockParams {
+    fn default() -> Self {
+        Self {
+            goal_seperation: 625.0,
+            goal_alignment: 2500.0,
+            goal_cohesion: 2500.0,
+            seperation_view_angle: TAU,
+            alignment_view_angle: TAU,
+            cohesion_view_angle: TAU,
+            neighbor_mode: NeighborMode::Metric,
+            seperation_falloff: Falloff::None,
+            alignment_falloff: Falloff::None,
+            cohesion_falloff: Falloff::None,
+        }
+    }
+}
+
+/// Amount of samples a [`Falloff::Curve`] is made of.
+pub const FALLOFF_CURVE_SAMPLES: usize = 32;
+
+// Added to the squared distance of [`Falloff::InverseSquare`], lower values fall off more sharply
+const INVERSE_SQUARE_SOFTENING: f32 = 0.1;
+
+/// Weight of a neighbour by its distance, relative to the radius of a rule.
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub enum Falloff {
+    /// Neighbours count fully up to the edge of the radius.
+    /// Seperation still pushes away harder from closer neighbours, by the inverse square of their distance.
+    #[default]
+    None,
+    /// Fades linearly from the center to the edge.
+    Linear,
+    /// Fades along a smoothstep, flat at the center and at the edge.
+    Smoothstep,
+    /// Inverse square of the distance, softened to 1 at the center and shifted to reach zero at the edge.
+    InverseSquare,
+    /// Evenly spaced weights from the center to the edge, linearly interpolated.
+    Curve([f32; FALLOFF_CURVE_SAMPLES]),
+}
+
+impl Falloff {
+    /// Weight at `t`, the distance relative to the radius. 1 at the center, 0 at the edge, except for [`Self::None`] and curves.
+    #[inline(always)]
+    pub fn weight(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Self::None => 1.0,
+            Self::Linear => 1.0 - t,
+            Self::Smoothstep => 1.0 - t * t * (3.0 - 2.0 * t),
+            Self::InverseSquare => INVERSE_SQUARE_SOFTENING * (1.0 - t * t) / (t * t + INVERSE_SQUARE_SOFTENING),
+            Self::Curve(samples) => {
+                let x = t * (FALLOFF_CURVE_SAMPLES - 1) as f32;
+                let i = (x as usize).min(FALLOFF_CURVE_SAMPLES - 2);
+                samples[i] + (samples[i + 1] - samples[i]) * (x - i as f32)
+            }
+        }
+    }
+}
+
+/// Most neighbours a boid can react to in [`NeighborMode::Topological`].
+pub const MAX_NEAREST_NEIGHBORS: u32 = 32;
+
+/// How boids of a flock pick the neighbours they react to.
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub enum NeighborMode {
+    /// Every boid within the distance of a rule.
+    #[default]
+    Metric,
+    /// The `count` nearest boids within `max_distance` (squared), however dense or spread out the flock is.
+    /// Alignment and cohesion apply to all of them, seperation only to those within `goal_seperation`.
+    /// Boids outside of every view angle aren't counted, rules with a narrower view angle may see fewer of them.
+    /// `count` is limited to [`MAX_NEAREST_NEIGHBORS`].
+    Topological { count: u32, max_distance: f32 },
+}
+
+/// How boids of a flock react to boids of another flock.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum RelationKind {
+    /// Boids of the other flock are not perceived at all.
+    #[default]
+    Ignore,
+    /// Boids keep their distance from the other flock, but don't align or cohere with it.
+    Separate,
+    /// Boids steer away from nearby boids of the other flock.
+    Flee,
+    /// Boids steer towards the nearest boid of the other flock.
+    Chase,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falloffs_stay_bounded_from_center_to_edge() {
+        let curve = std::array::from_fn(|i| 1.0 - i as f32 / (FALLOFF_CURVE_SAMPLES - 1) as f32);
+        for falloff in [Falloff::Linear, Falloff::Smoothstep, Falloff::InverseSquare, Falloff::Curve(curve)] {
+            assert_eq!(falloff.weight(0.0), 1.0, "{falloff:?} at the center");
+            assert_eq!(falloff.weight(1.0), 0.0, "{falloff:?} at the edge");
+            let mut last = 1.0;
+            for step in 1..=100 {
+                let weight = falloff.weight(step as f32 / 100.0);
+                assert!((0.0..=last).contains(&weight), "{falloff:?} rises or leaves [0, 1] at {step}%: {weight}");
+                last = weight;
+            }
+        }
+        assert_eq!(Falloff::None.weight(0.0), 1.0);
+        assert_eq!(Falloff::None.weight(1.0), 1.0);
+    }
+}
@@ -0,0 +1,107 @@
+use glam::*;
+use super::SpatialStructure;
+use crate::Dimensions;
+
+// Ranges this small are scanned instead of split further
+const LEAF_SIZE: usize = 8;
+// Enough for balanced trees over any u32 amount of positions
+const MAX_STACK: usize = 64;
+
+// Implicit, balanced k-d tree: every range `lo..hi` is split at its median `(lo + hi) / 2`,
+// on the axis matching its depth
+pub struct KdTree {
+    dimensions: Dimensions,
+    items: Vec<u32>,
+    points: Vec<Vec3>,
+}
+
+impl KdTree {
+    pub fn new(dimensions: Dimensions) -> Self {
+        Self {
+            dimensions,
+            items: Vec::new(),
+            points: Vec::new(),
+        }
+    }
+    
+    #[inline(always)]
+    fn axis(&self, depth: u32) -> usize {
+        match self.dimensions {
+            Dimensions::D2 => (depth % 2) as usize,
+            Dimensions::D3 => (depth % 3) as usize,
+        }
+    }
+}
+
+impl SpatialStructure for KdTree {
+    fn rebuild(&mut self, positions: &[Vec3]) {
+        self.items.clear();
+        self.items.extend(0..positions.len() as u32);
+        self.points.clear();
+        self.points.extend_from_slice(positions);
+        
+        let mut stack = [(0usize, 0usize, 0u32); MAX_STACK];
+        let mut stack_len = 0;
+        stack[stack_len] = (0, positions.len(), 0);
+        stack_len += 1;
+        while stack_len > 0 {
+            stack_len -= 1;
+            let (lo, hi, depth) = stack[stack_len];
+            if hi - lo <= LEAF_SIZE { continue; }
+            
+            let axis = self.axis(depth);
+            let mid = (lo + hi) / 2;
+            let points = &self.points;
+            self.items[lo..hi].select_nth_unstable_by(mid - lo, |&a, &b| {
+                points[a as usize][axis].total_cmp(&points[b as usize][axis])
+            });
+            
+            stack[stack_len] = (lo, mid, depth + 1);
+            stack[stack_len + 1] = (mid + 1, hi, depth + 1);
+            stack_len += 2;
+        }
+    }
+    
+    #[inline(always)]
+    fn for_each_neighbor<F: FnMut(&[u32])>(&self, position: Vec3, radius: f32, mut visit: F) {
+        let radius_sq = radius * radius;
+        
+        let mut stack = [(0usize, 0usize, 0u32); MAX_STACK];
+        let mut stack_len = 0;
+        stack[stack_len] = (0, self.items.len(), 0);
+        stack_len += 1;
+        while stack_len > 0 {
+            stack_len -= 1;
+            let (lo, hi, depth) = stack[stack_len];
+            if hi - lo <= LEAF_SIZE {
+                visit(&self.items[lo..hi]);
+                continue;
+            }
+            
+            let axis = self.axis(depth);
+            let mid = (lo + hi) / 2;
+            let split_item = &self.items[mid];
+            let split = self.points[*split_item as usize];
+            if split.distance_squared(position) <= radius_sq {
+                visit(std::slice::from_ref(split_item));
+            }
+            
+            // Only descend into halves the query sphere reaches
+            let offset = position[axis] - split[axis];
+            if offset - radius <= 0.0 {
+                stack[stack_len] = (lo, mid, depth + 1);
+                stack_len += 1;
+            }
+            if offset + radius >= 0.0 {
+                stack[stack_len] = (mid + 1, hi, depth + 1);
+                stack_len += 1;
+            }
+        }
+    }
+    
+    fn get_cell_size(&self) -> f32 {
+        0.0
+    }
+    
+    fn set_cell_size(&mut self, _: f32) {}
+}
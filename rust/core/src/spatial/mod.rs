@@ -0,0 +1,196 @@
+use glam::*;
+
+use crate::Dimensions;
+
+pub mod kd_tree;
+pub mod tree;
+pub mod ultra_hash;
+pub mod uniform_grid;
+
+pub use kd_tree::*;
+pub use tree::*;
+pub use ultra_hash::*;
+pub use uniform_grid::*;
+
+// Spatial data structure trait for different implementations
+pub trait SpatialStructure {
+    /// Rebuild the spatial structure with new positions
+    fn rebuild(&mut self, positions: &[Vec3]);
+    
+    /// Visit neighbors within radius of a position without allocating.
+    /// `visit` receives slices of indices into the positions of the last rebuild, possibly including some farther away.
+    fn for_each_neighbor<F: FnMut(&[u32])>(&self, position: Vec3, radius: f32, visit: F);
+    
+    /// Query neighbors within radius of a position, collected into a new `Vec`.
+    /// Prefer [`Self::for_each_neighbor`] in hot loops.
+    fn query_neighbors(&self, position: Vec3, radius: f32) -> Vec<u32> {
+        let mut neighbors = Vec::new();
+        self.for_each_neighbor(position, radius, |indices| neighbors.extend_from_slice(indices));
+        neighbors
+    }
+    
+    /// Get the optimal cell size for this structure
+    fn get_cell_size(&self) -> f32;
+    
+    /// Set the cell size, structures without cells ignore this
+    fn set_cell_size(&mut self, cell_size: f32);
+}
+
+/// Available spatial structures, selectable per flock.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum SpatialIndexKind {
+    /// Hashed grid, only occupied cells cost memory. Good default for sparse, open world flocks.
+    #[default]
+    HashGrid,
+    /// Dense grid over the flock bounds, built with a counting sort. Best for dense, compact schools.
+    UniformGrid,
+    /// Quadtree for 2D flocks, octree for 3D flocks. Adapts to uneven densities.
+    Tree,
+    /// K-d tree, balanced by median splits. Adapts to uneven densities without any cell size.
+    KdTree,
+}
+
+/// A spatial structure picked at runtime through [`SpatialIndexKind`].
+pub enum SpatialIndex {
+    HashGrid(UltraSpatialHash),
+    UniformGrid(UniformGrid),
+    Tree(SpatialTree),
+    KdTree(KdTree),
+}
+
+impl SpatialIndex {
+    pub fn new(kind: SpatialIndexKind, cell_size: f32, dimensions: Dimensions) -> Self {
+        match kind {
+            SpatialIndexKind::HashGrid => Self::HashGrid(UltraSpatialHash::new(cell_size, dimensions)),
+            SpatialIndexKind::UniformGrid => Self::UniformGrid(UniformGrid::new(cell_size, dimensions)),
+            SpatialIndexKind::Tree => Self::Tree(SpatialTree::new(dimensions)),
+            SpatialIndexKind::KdTree => Self::KdTree(KdTree::new(dimensions)),
+        }
+    }
+    
+    #[inline(always)]
+    pub fn kind(&self) -> SpatialIndexKind {
+        match self {
+            Self::HashGrid(_) => SpatialIndexKind::HashGrid,
+            Self::UniformGrid(_) => SpatialIndexKind::UniformGrid,
+            Self::Tree(_) => SpatialIndexKind::Tree,
+            Self::KdTree(_) => SpatialIndexKind::KdTree,
+        }
+    }
+}
+
+impl SpatialStructure for SpatialIndex {
+    fn rebuild(&mut self, positions: &[Vec3]) {
+        match self {
+            Self::HashGrid(index) => index.rebuild(positions),
+            Self::UniformGrid(index) => index.rebuild(positions),
+            Self::Tree(index) => index.rebuild(positions),
+            Self::KdTree(index) => index.rebuild(positions),
+        }
+    }
+    
+    #[inline(always)]
+    fn for_each_neighbor<F: FnMut(&[u32])>(&self, position: Vec3, radius: f32, visit: F) {
+        match self {
+            Self::HashGrid(index) => index.for_each_neighbor(position, radius, visit),
+            Self::UniformGrid(index) => index.for_each_neighbor(position, radius, visit),
+            Self::Tree(index) => index.for_each_neighbor(position, radius, visit),
+            Self::KdTree(index) => index.for_each_neighbor(position, radius, visit),
+        }
+    }
+    
+    fn get_cell_size(&self) -> f32 {
+        match self {
+            Self::HashGrid(index) => index.get_cell_size(),
+            Self::UniformGrid(index) => index.get_cell_size(),
+            Self::Tree(index) => index.get_cell_size(),
+            Self::KdTree(index) => index.get_cell_size(),
+        }
+    }
+    
+    fn set_cell_size(&mut self, cell_size: f32) {
+        match self {
+            Self::HashGrid(index) => index.set_cell_size(cell_size),
+            Self::UniformGrid(index) => index.set_cell_size(cell_size),
+            Self::Tree(index) => index.set_cell_size(cell_size),
+            Self::KdTree(index) => index.set_cell_size(cell_size),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Rng;
+    
+    const KINDS: [SpatialIndexKind; 4] = [
+        SpatialIndexKind::HashGrid,
+        SpatialIndexKind::UniformGrid,
+        SpatialIndexKind::Tree,
+        SpatialIndexKind::KdTree,
+    ];
+    
+    // Indices within `radius` of `position` as found by `index`, every index once
+    fn neighbors_within(index: &SpatialIndex, positions: &[Vec3], position: Vec3, radius: f32) -> Vec<u32> {
+        let mut neighbors: Vec<u32> = index.query_neighbors(position, radius).into_iter()
+            .filter(|&i| positions[i as usize].distance_squared(position) <= radius * radius)
+            .collect();
+        neighbors.sort_unstable();
+        neighbors
+    }
+    
+    fn assert_backends_match(dimensions: Dimensions) {
+        let mut rng = Rng(0x2545f491);
+        let flat = |v: Vec3| if dimensions == Dimensions::D2 { v.with_z(0.0) } else { v };
+        // A loose cloud, a dense cluster and boids right on top of each other
+        let mut positions: Vec<Vec3> = (0..300).map(|_| flat(rng.vec3(500.0))).collect();
+        positions.extend((0..200).map(|_| flat(Vec3::splat(120.0) + rng.vec3(15.0))));
+        positions.extend([Vec3::ZERO; 5]);
+        
+        let cell_size = 40.0;
+        let mut indices = KINDS.map(|kind| SpatialIndex::new(kind, cell_size, dimensions));
+        for index in indices.iter_mut() {
+            index.rebuild(&positions);
+        }
+        
+        let queries: Vec<Vec3> = positions.iter().step_by(7).copied().chain((0..50).map(|_| flat(rng.vec3(600.0)))).collect();
+        // Radii below, at and past the cell size, up to past the whole cloud
+        for radius in [15.0, cell_size, 90.0, 2000.0] {
+            for &position in &queries {
+                let expected: Vec<u32> = (0..positions.len() as u32)
+                    .filter(|&i| positions[i as usize].distance_squared(position) <= radius * radius)
+                    .collect();
+                for index in &indices {
+                    let found = neighbors_within(index, &positions, position, radius);
+                    assert_eq!(found, expected, "{:?} {dimensions:?} neighbours within {radius} of {position}", index.kind());
+                }
+            }
+        }
+    }
+    
+    #[test]
+    fn backends_find_the_same_neighbors_2d() {
+        assert_backends_match(Dimensions::D2);
+    }
+    
+    #[test]
+    fn backends_find_the_same_neighbors_3d() {
+        assert_backends_match(Dimensions::D3);
+    }
+    
+    #[test]
+    fn far_positions_and_huge_radii_find_every_neighbor() {
+        for dimensions in [Dimensions::D2, Dimensions::D3] {
+            let positions = [Vec3::ZERO, Vec3::splat(-1e30), Vec3::splat(1e30), Vec3::new(1e30, -1e30, 0.0)];
+            for kind in KINDS {
+                let mut index = SpatialIndex::new(kind, 40.0, dimensions);
+                index.rebuild(&positions);
+                for position in [Vec3::ZERO, Vec3::splat(1e30), Vec3::splat(-3e38)] {
+                    let mut found = index.query_neighbors(position, f32::MAX);
+                    found.sort_unstable();
+                    assert_eq!(found, [0, 1, 2, 3], "{kind:?} {dimensions:?} neighbours of {position}");
+                }
+            }
+        }
+    }
+}
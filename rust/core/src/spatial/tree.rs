@@ -0,0 +1,184 @@
+use glam::*;
+use super::SpatialStructure;
+use crate::Dimensions;
+
+// Nodes split once they hold more than this many positions
+const LEAF_CAPACITY: usize = 16;
+// Stops splitting stacked positions forever
+const MAX_DEPTH: u32 = 16;
+
+#[derive(Clone, Copy)]
+struct TreeNode {
+    min: Vec3,
+    max: Vec3,
+    // Index of the first of 4 (2D) or 8 (3D) consecutive children, 0 for leaves
+    first_child: u32,
+    // Range into `SpatialTree::items` covered by this node
+    start: u32,
+    end: u32,
+}
+
+// Quadtree (2D) / octree (3D) with bucketed leaves
+pub struct SpatialTree {
+    dimensions: Dimensions,
+    nodes: Vec<TreeNode>,
+    items: Vec<u32>,
+    // Scratch buffers for partitioning, kept around to avoid allocations
+    scratch: Vec<u32>,
+    build_stack: Vec<(u32, u32)>,
+}
+
+impl SpatialTree {
+    pub fn new(dimensions: Dimensions) -> Self {
+        Self {
+            dimensions,
+            nodes: Vec::new(),
+            items: Vec::new(),
+            scratch: Vec::new(),
+            build_stack: Vec::new(),
+        }
+    }
+    
+    #[inline(always)]
+    fn child_count(&self) -> usize {
+        match self.dimensions {
+            Dimensions::D2 => 4,
+            Dimensions::D3 => 8,
+        }
+    }
+    
+    #[inline(always)]
+    fn child_code(&self, pos: Vec3, center: Vec3) -> usize {
+        let mut code = (pos.x >= center.x) as usize | ((pos.y >= center.y) as usize) << 1;
+        if self.dimensions == Dimensions::D3 {
+            code |= ((pos.z >= center.z) as usize) << 2;
+        }
+        code
+    }
+    
+    #[inline(always)]
+    fn child_bounds(&self, min: Vec3, max: Vec3, code: usize) -> (Vec3, Vec3) {
+        let center = (min + max) * 0.5;
+        let mut child_min = min;
+        let mut child_max = center;
+        if code & 1 != 0 { child_min.x = center.x; child_max.x = max.x; }
+        if code & 2 != 0 { child_min.y = center.y; child_max.y = max.y; }
+        match self.dimensions {
+            Dimensions::D2 => { child_min.z = min.z; child_max.z = max.z; }
+            Dimensions::D3 => if code & 4 != 0 { child_min.z = center.z; child_max.z = max.z; },
+        }
+        (child_min, child_max)
+    }
+    
+    // Splits a node by bucketing its items into children with a counting sort
+    fn split(&mut self, node_idx: usize, positions: &[Vec3]) {
+        let node = self.nodes[node_idx];
+        let center = (node.min + node.max) * 0.5;
+        let child_count = self.child_count();
+        let (start, end) = (node.start as usize, node.end as usize);
+        
+        let mut counts = [0u32; 8];
+        for &item in &self.items[start..end] {
+            counts[self.child_code(positions[item as usize], center)] += 1;
+        }
+        
+        let mut offsets = [0u32; 8];
+        let mut offset = node.start;
+        for code in 0..child_count {
+            offsets[code] = offset;
+            offset += counts[code];
+        }
+        
+        let first_child = self.nodes.len() as u32;
+        for code in 0..child_count {
+            let (min, max) = self.child_bounds(node.min, node.max, code);
+            self.nodes.push(TreeNode {
+                min,
+                max,
+                first_child: 0,
+                start: offsets[code],
+                end: offsets[code] + counts[code],
+            });
+        }
+        self.nodes[node_idx].first_child = first_child;
+        
+        self.scratch.clear();
+        self.scratch.extend_from_slice(&self.items[start..end]);
+        for i in 0..self.scratch.len() {
+            let item = self.scratch[i];
+            let slot = &mut offsets[self.child_code(positions[item as usize], center)];
+            self.items[*slot as usize] = item;
+            *slot += 1;
+        }
+    }
+}
+
+impl SpatialStructure for SpatialTree {
+    fn rebuild(&mut self, positions: &[Vec3]) {
+        self.nodes.clear();
+        self.items.clear();
+        if positions.is_empty() { return; }
+        
+        self.items.extend(0..positions.len() as u32);
+        let (min, max) = positions.iter().fold(
+            (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+            |(min, max), &pos| (min.min(pos), max.max(pos)),
+        );
+        self.nodes.push(TreeNode {
+            min,
+            max,
+            first_child: 0,
+            start: 0,
+            end: positions.len() as u32,
+        });
+        
+        let mut stack = std::mem::take(&mut self.build_stack);
+        stack.clear();
+        stack.push((0, 0));
+        while let Some((node_idx, depth)) = stack.pop() {
+            let node = self.nodes[node_idx as usize];
+            if (node.end - node.start) as usize <= LEAF_CAPACITY || depth >= MAX_DEPTH {
+                continue;
+            }
+            self.split(node_idx as usize, positions);
+            let first_child = self.nodes[node_idx as usize].first_child;
+            for child in 0..self.child_count() as u32 {
+                stack.push((first_child + child, depth + 1));
+            }
+        }
+        self.build_stack = stack;
+    }
+    
+    #[inline(always)]
+    fn for_each_neighbor<F: FnMut(&[u32])>(&self, position: Vec3, radius: f32, mut visit: F) {
+        if self.nodes.is_empty() { return; }
+        
+        let radius_sq = radius * radius;
+        let mut stack = [0u32; 8 * MAX_DEPTH as usize + 8];
+        let mut stack_len = 1;
+        while stack_len > 0 {
+            stack_len -= 1;
+            let node = &self.nodes[stack[stack_len] as usize];
+            if node.start == node.end { continue; }
+            
+            // Skip nodes whose bounds are farther away than the radius
+            let closest = position.clamp(node.min, node.max);
+            if closest.distance_squared(position) > radius_sq { continue; }
+            
+            if node.first_child == 0 {
+                visit(&self.items[node.start as usize..node.end as usize]);
+            } else {
+                for child in 0..self.child_count() as u32 {
+                    stack[stack_len] = node.first_child + child;
+                    stack_len += 1;
+                }
+            }
+        }
+    }
+    
+    fn get_cell_size(&self) -> f32 {
+        0.0
+    }
+    
+    fn set_cell_size(&mut self, _: f32) {}
+}
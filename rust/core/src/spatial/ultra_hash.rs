@@ -0,0 +1,156 @@
+use glam::*;
+use rustc_hash::FxHashMap;
+use super::SpatialStructure;
+use crate::Dimensions;
+
+// Hashed grid, only occupied cells get a bucket
+pub struct UltraSpatialHash {
+    cell_size: f32,
+    inv_cell_size: f32,
+    dimensions: Dimensions,
+    buckets: FxHashMap<u64, Vec<u32>>,
+    bucket_pool: Vec<Vec<u32>>, // Reuse vectors to eliminate allocations
+    // Bounds of the occupied cells, queries never walk past them
+    min_cell: IVec3,
+    max_cell: IVec3,
+}
+
+impl UltraSpatialHash {
+    pub fn new(cell_size: f32, dimensions: Dimensions) -> Self {
+        Self {
+            cell_size,
+            inv_cell_size: 1.0 / cell_size,
+            dimensions,
+            buckets: FxHashMap::default(),
+            bucket_pool: Vec::with_capacity(2000), // Pre-allocate bucket pool
+            min_cell: IVec3::MAX,
+            max_cell: IVec3::MIN,
+        }
+    }
+    
+    #[inline(always)]
+    fn hash_cell_3d(x: i32, y: i32, z: i32) -> u64 {
+        // 21 bits per axis (supports large worlds)
+        (((x as u64) & 0x1fffff) << 42) |
+        (((y as u64) & 0x1fffff) << 21) |
+        ((z as u64) & 0x1fffff)
+    }
+    
+    #[inline(always)]
+    fn hash_cell_2d(x: i32, y: i32) -> u64 {
+        ((x as u32 as u64) << 32) | (y as u32 as u64)
+    }
+    
+    // Casts saturate, so far away positions end up in the outermost cells
+    #[inline(always)]
+    fn cell_coords(&self, pos: Vec3) -> IVec3 {
+        let cell = (pos * self.inv_cell_size).floor().as_ivec3();
+        match self.dimensions {
+            Dimensions::D2 => cell.with_z(0),
+            Dimensions::D3 => cell,
+        }
+    }
+    
+    #[inline(always)]
+    fn hash_cell(&self, cell: IVec3) -> u64 {
+        match self.dimensions {
+            Dimensions::D2 => Self::hash_cell_2d(cell.x, cell.y),
+            Dimensions::D3 => Self::hash_cell_3d(cell.x, cell.y, cell.z),
+        }
+    }
+    
+    // Cells within `radius` of `pos`, clamped to the occupied ones. `None` when there are fewer buckets than cells to walk
+    #[inline(always)]
+    fn cell_range(&self, pos: Vec3, radius: f32) -> Option<(IVec3, IVec3)> {
+        let grid_radius = (radius * self.inv_cell_size).ceil() as i32;
+        let center = self.cell_coords(pos);
+        // Saturating, a huge radius or a far away position would overflow the cell coordinates
+        let min = IVec3::from_array(center.to_array().map(|c| c.saturating_sub(grid_radius))).max(self.min_cell);
+        let max = IVec3::from_array(center.to_array().map(|c| c.saturating_add(grid_radius))).min(self.max_cell);
+        let span = (max.as_dvec3() - min.as_dvec3() + DVec3::ONE).max(DVec3::ZERO);
+        let cells = match self.dimensions {
+            Dimensions::D2 => span.x * span.y,
+            Dimensions::D3 => span.x * span.y * span.z,
+        };
+        (cells <= self.buckets.len() as f64).then_some((min, max))
+    }
+    
+    #[inline(always)]
+    pub fn for_each_neighbor_3d<F: FnMut(&[u32])>(&self, pos: Vec3, radius: f32, mut visit: F) {
+        let Some((min, max)) = self.cell_range(pos, radius) else {
+            self.buckets.values().for_each(|bucket| visit(bucket));
+            return;
+        };
+        
+        // Iterate through neighboring cells
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    if let Some(bucket) = self.buckets.get(&Self::hash_cell_3d(x, y, z)) {
+                        visit(bucket);
+                    }
+                }
+            }
+        }
+    }
+    
+    #[inline(always)]
+    pub fn for_each_neighbor_2d<F: FnMut(&[u32])>(&self, pos: Vec3, radius: f32, mut visit: F) {
+        let Some((min, max)) = self.cell_range(pos, radius) else {
+            self.buckets.values().for_each(|bucket| visit(bucket));
+            return;
+        };
+        
+        // Only iterate over x and y for 2D, 9 cells instead of 27 for the usual radius
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                if let Some(bucket) = self.buckets.get(&Self::hash_cell_2d(x, y)) {
+                    visit(bucket);
+                }
+            }
+        }
+    }
+}
+
+impl SpatialStructure for UltraSpatialHash {
+    fn rebuild(&mut self, positions: &[Vec3]) {
+        // Return buckets to pool
+        for (_, mut bucket) in self.buckets.drain() {
+            bucket.clear();
+            if bucket.capacity() >= 8 && bucket.capacity() <= 64 {
+                self.bucket_pool.push(bucket);
+            }
+        }
+        
+        // Populate with new positions
+        self.min_cell = IVec3::MAX;
+        self.max_cell = IVec3::MIN;
+        for (i, &pos) in positions.iter().enumerate() {
+            let cell = self.cell_coords(pos);
+            self.min_cell = self.min_cell.min(cell);
+            self.max_cell = self.max_cell.max(cell);
+            let hash = self.hash_cell(cell);
+            let bucket = self.buckets.entry(hash).or_insert_with(|| {
+                self.bucket_pool.pop().unwrap_or_else(|| Vec::with_capacity(16))
+            });
+            bucket.push(i as u32);
+        }
+    }
+    
+    #[inline(always)]
+    fn for_each_neighbor<F: FnMut(&[u32])>(&self, position: Vec3, radius: f32, visit: F) {
+        match self.dimensions {
+            Dimensions::D2 => self.for_each_neighbor_2d(position, radius, visit),
+            Dimensions::D3 => self.for_each_neighbor_3d(position, radius, visit),
+        }
+    }
+    
+    fn get_cell_size(&self) -> f32 {
+        self.cell_size
+    }
+    
+    fn set_cell_size(&mut self, cell_size: f32) {
+        self.cell_size = cell_size;
+        self.inv_cell_size = 1.0 / cell_size;
+    }
+}
@@ -0,0 +1,157 @@
+use glam::*;
+use super::SpatialStructure;
+use crate::Dimensions;
+
+// Upper bound of cells per indexed position, the cell size is doubled until the grid fits
+const MAX_CELLS_PER_POSITION: usize = 4;
+const MIN_MAX_CELLS: usize = 4096;
+
+// Dense grid over the bounds of the indexed positions, filled with a counting sort
+pub struct UniformGrid {
+    cell_size: f32,
+    dimensions: Dimensions,
+    // Cell size actually used by the last rebuild, may be larger than `cell_size` for very spread out positions
+    inv_grid_cell_size: f32,
+    origin: Vec3,
+    grid_dims: IVec3,
+    // Prefix sum, entries of cell `c` are `entries[cell_start[c]..cell_start[c + 1]]`
+    cell_start: Vec<u32>,
+    entries: Vec<u32>,
+    cell_of: Vec<u32>,
+    cursor: Vec<u32>,
+}
+
+impl UniformGrid {
+    pub fn new(cell_size: f32, dimensions: Dimensions) -> Self {
+        Self {
+            cell_size,
+            dimensions,
+            inv_grid_cell_size: 1.0 / cell_size,
+            origin: Vec3::ZERO,
+            grid_dims: IVec3::ZERO,
+            cell_start: Vec::new(),
+            entries: Vec::new(),
+            cell_of: Vec::new(),
+            cursor: Vec::new(),
+        }
+    }
+    
+    /// Indices of the positions of the last rebuild, ordered by cell.
+    #[inline(always)]
+    pub fn sorted_indices(&self) -> &[u32] {
+        &self.entries
+    }
+    
+    /// Prefix sum over the cells, entries of cell `c` are `sorted_indices()[cell_offsets()[c]..cell_offsets()[c + 1]]`.
+    #[inline(always)]
+    pub fn cell_offsets(&self) -> &[u32] {
+        &self.cell_start
+    }
+    
+    #[inline(always)]
+    fn cell_coords(&self, pos: Vec3) -> IVec3 {
+        let coords = ((pos - self.origin) * self.inv_grid_cell_size).floor().as_ivec3();
+        match self.dimensions {
+            Dimensions::D2 => coords.with_z(0),
+            Dimensions::D3 => coords,
+        }
+    }
+    
+    #[inline(always)]
+    fn cell_index(&self, coords: IVec3) -> usize {
+        (coords.x + self.grid_dims.x * (coords.y + self.grid_dims.y * coords.z)) as usize
+    }
+}
+
+impl SpatialStructure for UniformGrid {
+    fn rebuild(&mut self, positions: &[Vec3]) {
+        self.entries.clear();
+        self.cell_start.clear();
+        self.grid_dims = IVec3::ZERO;
+        if positions.is_empty() { return; }
+        
+        // Non finite positions have no place in the bounds, they end up clamped into the border cells
+        let (min, max) = positions.iter().filter(|pos| pos.is_finite()).fold(
+            (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+            |(min, max), &pos| (min.min(pos), max.max(pos)),
+        );
+        let (min, max) = if min.is_finite() { (min, max) } else { (Vec3::ZERO, Vec3::ZERO) };
+        // Positions at both ends of the float range would overflow the extent
+        let extent = (max - min).min(Vec3::splat(f32::MAX));
+        let extent = match self.dimensions {
+            Dimensions::D2 => extent.with_z(0.0),
+            Dimensions::D3 => extent,
+        };
+        
+        // Grow the cells until the grid stays proportional to the amount of positions
+        let max_cells = (positions.len() * MAX_CELLS_PER_POSITION).max(MIN_MAX_CELLS);
+        let mut grid_cell_size = if self.cell_size > 0.0 && self.cell_size.is_finite() {
+            self.cell_size
+        } else {
+            extent.max_element().max(1.0)
+        };
+        let grid_dims = loop {
+            // Counted in floats, positions far apart would overflow integer dimensions
+            let dims = (extent / grid_cell_size).floor() + Vec3::ONE;
+            if dims.x * dims.y * dims.z <= max_cells as f32 { break dims.as_ivec3(); }
+            grid_cell_size *= 2.0;
+        };
+        self.origin = min;
+        self.inv_grid_cell_size = 1.0 / grid_cell_size;
+        self.grid_dims = grid_dims;
+        
+        // Counting sort: count per cell, prefix sum, then scatter
+        let cell_count = grid_dims.x as usize * grid_dims.y as usize * grid_dims.z as usize;
+        self.cell_start.resize(cell_count + 1, 0);
+        self.cell_of.clear();
+        for &pos in positions {
+            let coords = self.cell_coords(pos).clamp(IVec3::ZERO, grid_dims - IVec3::ONE);
+            let cell = self.cell_index(coords);
+            self.cell_of.push(cell as u32);
+            self.cell_start[cell + 1] += 1;
+        }
+        for cell in 0..cell_count {
+            self.cell_start[cell + 1] += self.cell_start[cell];
+        }
+        
+        self.entries.resize(positions.len(), 0);
+        self.cursor.clear();
+        self.cursor.extend_from_slice(&self.cell_start);
+        for (i, &cell) in self.cell_of.iter().enumerate() {
+            let slot = &mut self.cursor[cell as usize];
+            self.entries[*slot as usize] = i as u32;
+            *slot += 1;
+        }
+    }
+    
+    #[inline(always)]
+    fn for_each_neighbor<F: FnMut(&[u32])>(&self, position: Vec3, radius: f32, mut visit: F) {
+        if self.entries.is_empty() { return; }
+        
+        // Clamp the covered cell range to the grid, positions outside only see the border cells they reach
+        let last = self.grid_dims - IVec3::ONE;
+        let min = self.cell_coords(position - Vec3::splat(radius)).max(IVec3::ZERO);
+        let max = self.cell_coords(position + Vec3::splat(radius)).min(last);
+        if min.cmpgt(max).any() { return; }
+        
+        for z in min.z..=max.z {
+            for y in min.y..=max.y {
+                let row = self.cell_index(ivec3(0, y, z));
+                let start = self.cell_start[row + min.x as usize] as usize;
+                let end = self.cell_start[row + max.x as usize + 1] as usize;
+                // Cells of a row are contiguous, so the whole x range is one slice
+                if start < end {
+                    visit(&self.entries[start..end]);
+                }
+            }
+        }
+    }
+    
+    fn get_cell_size(&self) -> f32 {
+        self.cell_size
+    }
+    
+    fn set_cell_size(&mut self, cell_size: f32) {
+        self.cell_size = cell_size;
+    }
+}
@@ -0,0 +1,19 @@
+// Helpers shared by the unit tests
+use glam::*;
+
+// Xorshift, good enough to scatter boids
+pub(crate) struct Rng(pub u32);
+
+impl Rng {
+    // Uniform in [-1, 1]
+    pub fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+    
+    pub fn vec3(&mut self, scale: f32) -> Vec3 {
+        Vec3::new(self.next(), self.next(), self.next()) * scale
+    }
+}
@@ -0,0 +1,76 @@
+use std::f32::consts::{PI, TAU};
+
+use boids_core::BoidInstance;
+use glam::*;
+
+/// How a boid turns to face along its velocity.
+#[derive(Clone, Copy, Debug)]
+pub struct BoidOrientation {
+    /// Direction the top of the boid is kept towards, only used in 3D.
+    pub up: Vec3,
+    /// Time constant of turning towards the velocity, in seconds. 0 turns instantly.
+    pub smoothing: f32,
+    /// Roll into turns at full lateral steering force, in radians. Only used in 3D.
+    pub max_bank: f32,
+}
+
+impl Default for BoidOrientation {
+    fn default() -> Self {
+        Self {
+            up: Vec3::Y,
+            smoothing: 0.0,
+            max_bank: 0.0,
+        }
+    }
+}
+
+impl BoidOrientation {
+    // Share of the remaining turn done within `delta` seconds
+    #[inline(always)]
+    fn turn_weight(&self, delta: f32) -> f32 {
+        if self.smoothing <= 0.0 {
+            return 1.0;
+        }
+        1.0 - (-delta / self.smoothing).exp()
+    }
+
+    /// Rotation facing along the boid's velocity with -Z as forward like Godot's own `look_at`, `None` while standing still.
+    ///
+    /// Boids roll by how much of their `max_force` pushes them sideways, up to `max_bank`.
+    pub fn rotation_3d(&self, boid: &BoidInstance) -> Option<Quat> {
+        let forward = boid.velocity.try_normalize()?;
+        let up = self.up.try_normalize().unwrap_or(Vec3::Y);
+        // Moving along the up vector leaves no way to tell up, fall back to any perpendicular one
+        let up = if forward.dot(up).abs() > 0.999 { up.any_orthonormal_vector() } else { up };
+        let back = -forward;
+        let right = up.cross(back).normalize();
+        let rotation = Quat::from_mat3(&Mat3::from_cols(right, back.cross(right), back));
+        if self.max_bank <= 0.0 {
+            return Some(rotation);
+        }
+
+        // Steering to the right tilts the top into the turn
+        let lateral = boid.force.dot(right) / boid.properties.max_force.max(f32::EPSILON);
+        let roll = Quat::from_axis_angle(forward, self.max_bank * lateral.clamp(-1.0, 1.0));
+        Some(roll * rotation)
+    }
+
+    /// Turns `current` towards [`Self::rotation_3d`] over `delta` seconds.
+    pub fn turn_3d(&self, current: Quat, boid: &BoidInstance, delta: f32) -> Quat {
+        let Some(target) = self.rotation_3d(boid) else { return current; };
+        current.slerp(target, self.turn_weight(delta)).normalize()
+    }
+
+    /// Angle facing along the boid's velocity with +X as forward like `Node2D.rotation`, `None` while standing still.
+    #[inline(always)]
+    pub fn angle_2d(&self, boid: &BoidInstance) -> Option<f32> {
+        Some(boid.velocity.xy().try_normalize()?.to_angle())
+    }
+
+    /// Turns `current` towards [`Self::angle_2d`] over `delta` seconds, along the shorter way around.
+    pub fn turn_2d(&self, current: f32, boid: &BoidInstance, delta: f32) -> f32 {
+        let Some(target) = self.angle_2d(boid) else { return current; };
+        let diff = (target - current + PI).rem_euclid(TAU) - PI;
+        current + diff * self.turn_weight(delta)
+    }
+}
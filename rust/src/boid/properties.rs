@@ -1,38 +1,526 @@
-use godot::prelude::*;
+use std::f32::consts::TAU;
 
-#[derive(Default, Clone, Debug, GodotClass)]
+use boids_core::{BoidParams, BoundsInstance, BoundsMode, BoundsShape, Falloff, FlockParams, NeighborMode, RelationKind, SpatialIndexKind, FALLOFF_CURVE_SAMPLES, MAX_NEAREST_NEIGHBORS};
+use glam::*;
+use godot::{classes::Curve, obj::Inherits, prelude::*};
+
+/// Stores `properties` in `slot`, moving the `changed` connection to `on_changed` over from the previous resource.
+pub(crate) fn swap_properties<R: Inherits<Resource>>(slot: &mut Option<Gd<R>>, properties: Option<Gd<R>>, on_changed: &Callable) {
+    if let Some(previous) = slot.take() {
+        let mut previous = previous.upcast::<Resource>();
+        if previous.is_connected("changed", on_changed) {
+            previous.disconnect("changed", on_changed);
+        }
+    }
+    if let Some(resource) = properties.as_ref() {
+        resource.clone().upcast::<Resource>().connect("changed", on_changed);
+    }
+    *slot = properties;
+}
+
+#[derive(Debug, GodotClass)]
 #[class(init, base=Resource)]
+/// Steering weights and limits of boids. Changes apply to running boids right away.
 pub struct BoidProperties {
     #[export]
-    #[init(val = 4.0)]
+    #[var(get, set = set_max_speed)]
+    #[init(val = 240.0)]
+    /// Maximum speed, in units per second.
     pub max_speed: f32,
     #[export]
-    #[init(val = 1.0)]
+    #[var(get, set = set_max_force)]
+    #[init(val = 3600.0)]
+    /// Maximum steering acceleration, in units per second squared.
     pub max_force: f32,
     #[export]
+    #[var(get, set = set_alignment)]
     #[init(val = 1.5)]
     pub alignment: f32,
     #[export]
+    #[var(get, set = set_cohesion)]
     #[init(val = 1.0)]
     pub cohesion: f32,
     #[export]
+    #[var(get, set = set_seperation)]
     #[init(val = 1.2)]
     pub seperation: f32,
     #[export]
+    #[var(get, set = set_targeting)]
     #[init(val = 0.8)]
     pub targeting: f32,
+    #[export]
+    #[var(get, set = set_avoidance)]
+    #[init(val = 2.0)]
+    /// Weight of steering around `BoidObstacle2D` / `BoidObstacle3D` nodes.
+    pub avoidance: f32,
+    #[export]
+    #[var(get, set = set_flee)]
+    #[init(val = 3.0)]
+    /// Weight of fleeing from `BoidPredator2D` / `BoidPredator3D` nodes.
+    pub flee: f32,
+    #[export]
+    #[var(get, set = set_wander)]
+    /// Weight of wandering around along the noise picked by the flock's `wander_seed`.
+    pub wander: f32,
+    base: Base<Resource>,
+}
+
+#[godot_api]
+impl BoidProperties {
+    #[func]
+    fn set_max_speed(&mut self, value: f32) {
+        self.max_speed = value;
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_max_force(&mut self, value: f32) {
+        self.max_force = value;
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_alignment(&mut self, value: f32) {
+        self.alignment = value;
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_cohesion(&mut self, value: f32) {
+        self.cohesion = value;
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_seperation(&mut self, value: f32) {
+        self.seperation = value;
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_targeting(&mut self, value: f32) {
+        self.targeting = value;
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_avoidance(&mut self, value: f32) {
+        self.avoidance = value;
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_flee(&mut self, value: f32) {
+        self.flee = value;
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_wander(&mut self, value: f32) {
+        self.wander = value;
+        self.base_mut().emit_changed();
+    }
 }
 
-#[derive(Default, Clone, Debug, GodotClass)]
+impl From<&BoidProperties> for BoidParams {
+    fn from(props: &BoidProperties) -> Self {
+        Self {
+            max_speed: props.max_speed,
+            max_force: props.max_force,
+            alignment: props.alignment,
+            cohesion: props.cohesion,
+            seperation: props.seperation,
+            targeting: props.targeting,
+            avoidance: props.avoidance,
+            flee: props.flee,
+            wander: props.wander,
+        }
+    }
+}
+
+#[derive(Debug, GodotClass)]
 #[class(tool, init, base=Resource)]
+/// Neighbour distances and view angles of a flock. Changes apply to running flocks right away.
+///
+/// Boids only perceive neighbours within the view angle of a rule, centered on their velocity. 360° has no blind spot.
+/// In `Topological` neighbour mode, boids align and cohere with their `neighbor_count` nearest neighbours instead,
+/// as far as `neighbor_max_distance`.
+/// The falloff of a rule fades how much neighbours count towards it, down to nothing at the edge of its distance.
 pub struct FlockProperties {
     #[export]
+    #[var(get, set = set_goal_seperation)]
     #[init(val = 625.0)]
     pub goal_seperation: f32,
     #[export]
+    #[var(get, set = set_goal_alignment)]
     #[init(val = 2500.0)]
     pub goal_alignment: f32,
     #[export]
+    #[var(get, set = set_goal_cohesion)]
     #[init(val = 2500.0)]
     pub goal_cohesion: f32,
-}
\ No newline at end of file
+    #[export(range = (0.0, 360.0, radians_as_degrees))]
+    #[var(get, set = set_seperation_view_angle)]
+    #[init(val = TAU)]
+    pub seperation_view_angle: f32,
+    #[export(range = (0.0, 360.0, radians_as_degrees))]
+    #[var(get, set = set_alignment_view_angle)]
+    #[init(val = TAU)]
+    pub alignment_view_angle: f32,
+    #[export(range = (0.0, 360.0, radians_as_degrees))]
+    #[var(get, set = set_cohesion_view_angle)]
+    #[init(val = TAU)]
+    pub cohesion_view_angle: f32,
+    #[export]
+    #[var(get, set = set_neighbor_mode)]
+    pub neighbor_mode: FlockNeighborMode,
+    #[export(range = (1.0, 32.0))]
+    #[var(get, set = set_neighbor_count)]
+    #[init(val = 7)]
+    /// Amount of nearest neighbours boids react to in `Topological` mode, not counting those outside every view angle.
+    pub neighbor_count: u32,
+    #[export]
+    #[var(get, set = set_neighbor_max_distance)]
+    #[init(val = 10000.0)]
+    /// Distance (squared) past which boids aren't neighbours in `Topological` mode, even among the nearest.
+    pub neighbor_max_distance: f32,
+    #[export]
+    #[var(get, set = set_seperation_falloff)]
+    pub seperation_falloff: FlockFalloff,
+    #[export]
+    #[var(get, set = set_seperation_falloff_curve)]
+    /// Weight of neighbours from the boid (0) to the edge of `goal_seperation` (1), with a `Curve` falloff.
+    pub seperation_falloff_curve: Option<Gd<Curve>>,
+    #[export]
+    #[var(get, set = set_alignment_falloff)]
+    pub alignment_falloff: FlockFalloff,
+    #[export]
+    #[var(get, set = set_alignment_falloff_curve)]
+    /// Weight of neighbours from the boid (0) to the edge of `goal_alignment` (1), with a `Curve` falloff.
+    pub alignment_falloff_curve: Option<Gd<Curve>>,
+    #[export]
+    #[var(get, set = set_cohesion_falloff)]
+    pub cohesion_falloff: FlockFalloff,
+    #[export]
+    #[var(get, set = set_cohesion_falloff_curve)]
+    /// Weight of neighbours from the boid (0) to the edge of `goal_cohesion` (1), with a `Curve` falloff.
+    pub cohesion_falloff_curve: Option<Gd<Curve>>,
+    base: Base<Resource>,
+}
+
+#[godot_api]
+impl FlockProperties {
+    #[func]
+    fn set_goal_seperation(&mut self, value: f32) {
+        self.goal_seperation = value;
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_goal_alignment(&mut self, value: f32) {
+        self.goal_alignment = value;
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_goal_cohesion(&mut self, value: f32) {
+        self.goal_cohesion = value;
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_seperation_view_angle(&mut self, value: f32) {
+        self.seperation_view_angle = value;
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_alignment_view_angle(&mut self, value: f32) {
+        self.alignment_view_angle = value;
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_cohesion_view_angle(&mut self, value: f32) {
+        self.cohesion_view_angle = value;
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_neighbor_mode(&mut self, value: FlockNeighborMode) {
+        self.neighbor_mode = value;
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_neighbor_count(&mut self, value: u32) {
+        self.neighbor_count = value.clamp(1, MAX_NEAREST_NEIGHBORS);
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_neighbor_max_distance(&mut self, value: f32) {
+        self.neighbor_max_distance = value;
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_seperation_falloff(&mut self, value: FlockFalloff) {
+        self.seperation_falloff = value;
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_seperation_falloff_curve(&mut self, value: Option<Gd<Curve>>) {
+        // Edits of the curve count as edits of the properties
+        let on_changed = self.base().callable("emit_changed");
+        swap_properties(&mut self.seperation_falloff_curve, value, &on_changed);
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_alignment_falloff(&mut self, value: FlockFalloff) {
+        self.alignment_falloff = value;
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_alignment_falloff_curve(&mut self, value: Option<Gd<Curve>>) {
+        let on_changed = self.base().callable("emit_changed");
+        swap_properties(&mut self.alignment_falloff_curve, value, &on_changed);
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_cohesion_falloff(&mut self, value: FlockFalloff) {
+        self.cohesion_falloff = value;
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_cohesion_falloff_curve(&mut self, value: Option<Gd<Curve>>) {
+        let on_changed = self.base().callable("emit_changed");
+        swap_properties(&mut self.cohesion_falloff_curve, value, &on_changed);
+        self.base_mut().emit_changed();
+    }
+}
+
+impl From<&FlockProperties> for FlockParams {
+    fn from(props: &FlockProperties) -> Self {
+        Self {
+            goal_seperation: props.goal_seperation,
+            goal_alignment: props.goal_alignment,
+            goal_cohesion: props.goal_cohesion,
+            seperation_view_angle: props.seperation_view_angle,
+            alignment_view_angle: props.alignment_view_angle,
+            cohesion_view_angle: props.cohesion_view_angle,
+            neighbor_mode: match props.neighbor_mode {
+                FlockNeighborMode::Metric => NeighborMode::Metric,
+                FlockNeighborMode::Topological => NeighborMode::Topological {
+                    count: props.neighbor_count,
+                    max_distance: props.neighbor_max_distance,
+                },
+            },
+            seperation_falloff: props.seperation_falloff.to_falloff(props.seperation_falloff_curve.as_ref()),
+            alignment_falloff: props.alignment_falloff.to_falloff(props.alignment_falloff_curve.as_ref()),
+            cohesion_falloff: props.cohesion_falloff.to_falloff(props.cohesion_falloff_curve.as_ref()),
+        }
+    }
+}
+
+/// How boids of a flock react to boids of another flock.
+#[derive(GodotConvert, Var, Export, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[godot(via = i64)]
+pub enum FlockRelationKind {
+    /// Boids of the other flock are not perceived at all.
+    #[default]
+    Ignore,
+    /// Boids keep their distance from the other flock, but don't align or cohere with it.
+    Separate,
+    /// Boids steer away from nearby boids of the other flock.
+    Flee,
+    /// Boids steer towards the nearest boid of the other flock.
+    Chase,
+}
+
+impl From<FlockRelationKind> for RelationKind {
+    fn from(kind: FlockRelationKind) -> Self {
+        match kind {
+            FlockRelationKind::Ignore => RelationKind::Ignore,
+            FlockRelationKind::Separate => RelationKind::Separate,
+            FlockRelationKind::Flee => RelationKind::Flee,
+            FlockRelationKind::Chase => RelationKind::Chase,
+        }
+    }
+}
+
+/// How much neighbours count towards a rule by their distance.
+#[derive(GodotConvert, Var, Export, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[godot(via = i64)]
+pub enum FlockFalloff {
+    /// Neighbours count fully up to the edge. Seperation still pushes harder away from closer neighbours.
+    #[default]
+    None,
+    /// Fades linearly to the edge.
+    Linear,
+    /// Fades smoothly, flat at the boid and at the edge.
+    Smoothstep,
+    /// Close neighbours count much more than far ones, fading to nothing at the edge.
+    InverseSquare,
+    /// Follows the falloff curve of the rule, no falloff without one.
+    Curve,
+}
+
+impl FlockFalloff {
+    // Bakes `curve` into samples for a `Curve` falloff
+    fn to_falloff(self, curve: Option<&Gd<Curve>>) -> Falloff {
+        match self {
+            Self::None => Falloff::None,
+            Self::Linear => Falloff::Linear,
+            Self::Smoothstep => Falloff::Smoothstep,
+            Self::InverseSquare => Falloff::InverseSquare,
+            Self::Curve => match curve {
+                Some(curve) => Falloff::Curve(std::array::from_fn(|i| {
+                    curve.sample(i as f32 / (FALLOFF_CURVE_SAMPLES - 1) as f32)
+                })),
+                None => Falloff::None,
+            },
+        }
+    }
+}
+
+/// How boids of a flock pick the neighbours they react to.
+#[derive(GodotConvert, Var, Export, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[godot(via = i64)]
+pub enum FlockNeighborMode {
+    /// Every boid within the distance of a rule. Dense flocks react to many neighbours, spread out ones to few.
+    #[default]
+    Metric,
+    /// A fixed amount of nearest boids, like starlings do. Keeps flocks together when they spread out.
+    Topological,
+}
+
+/// Spatial structure a flock uses to find neighbouring boids.
+#[derive(GodotConvert, Var, Export, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[godot(via = i64)]
+pub enum FlockSpatialIndex {
+    /// Hashed grid, only occupied cells are stored. Works well for any flock.
+    #[default]
+    HashGrid,
+    /// Dense grid over the flock's bounds. Fastest for compact, dense flocks.
+    UniformGrid,
+    /// Quadtree in 2D, octree in 3D. Suits unevenly spread flocks.
+    Tree,
+    /// Balanced k-d tree. Suits sparse flocks with very uneven density.
+    KdTree,
+}
+
+impl From<FlockSpatialIndex> for SpatialIndexKind {
+    fn from(kind: FlockSpatialIndex) -> Self {
+        match kind {
+            FlockSpatialIndex::HashGrid => SpatialIndexKind::HashGrid,
+            FlockSpatialIndex::UniformGrid => SpatialIndexKind::UniformGrid,
+            FlockSpatialIndex::Tree => SpatialIndexKind::Tree,
+            FlockSpatialIndex::KdTree => SpatialIndexKind::KdTree,
+        }
+    }
+}
+
+#[derive(Clone, Debug, GodotClass)]
+#[class(tool, init, base=Resource)]
+/// Rule describing how the flock owning this resource reacts to another flock.
+///
+/// Flocks ignore each other unless a relation says otherwise. Relations are one-sided,
+/// so a predator / prey setup needs a `Chase` relation on the predator flock and a `Flee` relation on the prey flock.
+pub struct FlockRelation {
+    #[export]
+    /// Path to the other flock, relative to the flock owning this relation.
+    pub flock: NodePath,
+    #[export]
+    #[init(val = FlockRelationKind::Separate)]
+    pub kind: FlockRelationKind,
+    #[export]
+    #[init(val = 625.0)]
+    /// Distance (squared) within which boids of the other flock are reacted to.
+    pub distance: f32,
+    #[export]
+    #[init(val = 1.0)]
+    /// Weight of the steering force produced by this relation.
+    pub weight: f32,
+}
+
+/// Shape of a flock's bounds.
+#[derive(GodotConvert, Var, Export, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[godot(via = i64)]
+pub enum FlockBoundsShape {
+    /// Box in 3D, rectangle in 2D.
+    #[default]
+    Box,
+    /// Sphere in 3D, circle in 2D.
+    Sphere,
+}
+
+/// What happens to boids reaching the edge of their flock's bounds.
+#[derive(GodotConvert, Var, Export, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[godot(via = i64)]
+pub enum FlockBoundsMode {
+    /// Boids steer back inside once within `margin` of the edge.
+    #[default]
+    Steer,
+    /// Boids leaving on one side come back in on the opposite side.
+    Wrap,
+    /// Boids are reflected off the edge.
+    Bounce,
+}
+
+impl From<FlockBoundsMode> for BoundsMode {
+    fn from(mode: FlockBoundsMode) -> Self {
+        match mode {
+            FlockBoundsMode::Steer => BoundsMode::Steer,
+            FlockBoundsMode::Wrap => BoundsMode::Wrap,
+            FlockBoundsMode::Bounce => BoundsMode::Bounce,
+        }
+    }
+}
+
+#[derive(Clone, Debug, GodotClass)]
+#[class(tool, init, base=Resource)]
+/// Volume the boids of a flock are kept in, centered on the flock and following its global transform.
+///
+/// 2D flocks only use the x and y components of `size`.
+pub struct FlockBounds {
+    #[export]
+    pub shape: FlockBoundsShape,
+    #[export]
+    #[init(val = Vector3::new(1024.0, 1024.0, 1024.0))]
+    /// Size of the box, centered on the flock.
+    pub size: Vector3,
+    #[export]
+    #[init(val = 512.0)]
+    /// Radius of the sphere.
+    pub radius: f32,
+    #[export]
+    pub mode: FlockBoundsMode,
+    #[export]
+    #[init(val = 64.0)]
+    /// Distance from the edge at which steering boids start turning back.
+    pub margin: f32,
+    #[export]
+    #[init(val = 2.0)]
+    /// Weight of the force steering boids back inside.
+    pub weight: f32,
+}
+
+impl FlockBounds {
+    /// The bounds as seen by the simulation, placed by the flock's `transform`. 2D flocks pass `flat` to drop z.
+    pub fn to_bounds_instance(&self, transform: Affine3A, flat: bool) -> BoundsInstance {
+        let shape = match self.shape {
+            FlockBoundsShape::Box => {
+                let size = vec3(self.size.x, self.size.y, if flat { 0.0 } else { self.size.z });
+                BoundsShape::Box { half_extents: size.abs() * 0.5 }
+            }
+            FlockBoundsShape::Sphere => BoundsShape::Sphere { radius: self.radius },
+        };
+        BoundsInstance::new(shape, self.mode.into(), transform, self.margin, self.weight)
+    }
+}
@@ -1,29 +1,89 @@
 use super::*;
-use crate::{get_singleton, to_glam_vec, BoidProperties, FlockProperties, FxIndexMap};
+use boids_core::{BoidInstance, BoundsInstance, FlockParams, SpatialIndexKind};
+use crate::{get_singleton, swap_properties, to_glam_affine_3d, to_glam_vec, BoidProperties, FlockBounds, FlockProperties, FlockRelation, FlockSpatialIndex};
+use crate::flock::BoidStore;
 
 #[derive(GodotClass)]
 #[class(init, base=Node3D)]
 pub struct Boid3D {
     #[export]
+    #[var(get, set = set_properties)]
     properties: Option<Gd<BoidProperties>>,
-    props: BoidProperties,
+    props: BoidParams,
+    #[export]
+    #[var(get, set = set_orient_to_velocity)]
+    /// Turns the boid to face along its velocity, -Z being forward like for `look_at`.
+    orient_to_velocity: bool,
+    #[export]
+    #[var(get, set = set_up_vector)]
+    #[init(val = Vector3::UP)]
+    /// Direction the top of the boid is kept towards while oriented.
+    up_vector: Vector3,
+    #[export]
+    #[var(get, set = set_orientation_smoothing)]
+    /// Time constant of turning towards the velocity, in seconds. 0 turns instantly.
+    orientation_smoothing: f32,
+    #[export(range = (0.0, 90.0, radians_as_degrees))]
+    #[var(get, set = set_max_bank_angle)]
+    /// Roll into turns at full sideways steering force.
+    max_bank_angle: f32,
+    // Velocity while the boid isn't simulated, its flock owns the velocity otherwise
     vel: Vec3,
     flock_id: Option<InstanceId>,
     base: Base<Node3D>,
 }
 
+impl Boid3D {
+    fn get_flock(&self) -> Option<Gd<Flock3D>> {
+        self.flock_id.and_then(|id| Gd::try_from_instance_id(id).ok())
+    }
+
+    // How the flock turns the boid node, `None` unless oriented along its velocity
+    fn get_orientation(&self) -> Option<BoidOrientation> {
+        self.orient_to_velocity.then(|| BoidOrientation {
+            up: to_glam_vec(self.up_vector),
+            smoothing: self.orientation_smoothing,
+            max_bank: self.max_bank_angle,
+        })
+    }
+
+    fn sync_orientation(&mut self) {
+        let (id, orientation) = (self.get_id(), self.get_orientation());
+        if let Some(mut flock) = self.get_flock() {
+            flock.bind_mut().boids.set_orientation(id, orientation);
+        }
+    }
+}
+
 #[godot_api]
 impl Boid3D {
     #[func]
     #[inline(always)]
     fn get_velocity(&self) -> Vector3 {
-        Vector3::new(self.vel.x, self.vel.y, self.vel.z)
+        let vel = self.get_boid_velocity();
+        Vector3::new(vel.x, vel.y, vel.z)
     }
 
     #[func]
     #[inline(always)]
     fn set_velocity(&mut self, new_velocity: Vector3) {
         self.vel = to_glam_vec(new_velocity);
+        let id = self.get_id();
+        if let Some(mut flock) = self.get_flock() {
+            if let Some(boid) = flock.bind_mut().boids.get_mut(id) {
+                boid.velocity = self.vel;
+            }
+        }
+    }
+
+    /// Moves the boid to `global_position` right away. Positions set any other way are overwritten by its flock.
+    #[func]
+    fn teleport(&mut self, global_position: Vector3) {
+        self.base_mut().set_global_position(global_position);
+        let id = self.get_id();
+        if let Some(mut flock) = self.get_flock() {
+            flock.bind_mut().boids.teleport(id, to_glam_vec(global_position));
+        }
     }
 
     #[func]
@@ -37,6 +97,48 @@ impl Boid3D {
     pub fn get_flock_id(&self) -> InstanceId {
         self.flock_id.expect("no flock id found set... this is a bug!")
     }
+
+    #[func]
+    fn set_properties(&mut self, properties: Option<Gd<BoidProperties>>) {
+        let on_changed = self.base().callable("on_properties_changed");
+        swap_properties(&mut self.properties, properties, &on_changed);
+        self.on_properties_changed();
+    }
+
+    #[func]
+    fn on_properties_changed(&mut self) {
+        self.props = self.properties.as_ref().map(|props| (&*props.bind()).into()).unwrap_or_default();
+        let id = self.get_id();
+        if let Some(mut flock) = self.get_flock() {
+            if let Some(boid) = flock.bind_mut().boids.get_mut(id) {
+                boid.properties = self.props;
+            }
+        }
+    }
+
+    #[func]
+    fn set_orient_to_velocity(&mut self, enabled: bool) {
+        self.orient_to_velocity = enabled;
+        self.sync_orientation();
+    }
+
+    #[func]
+    fn set_up_vector(&mut self, up: Vector3) {
+        self.up_vector = up;
+        self.sync_orientation();
+    }
+
+    #[func]
+    fn set_orientation_smoothing(&mut self, smoothing: f32) {
+        self.orientation_smoothing = smoothing.max(0.0);
+        self.sync_orientation();
+    }
+
+    #[func]
+    fn set_max_bank_angle(&mut self, angle: f32) {
+        self.max_bank_angle = angle.max(0.0);
+        self.sync_orientation();
+    }
 }
 
 #[godot_api]
@@ -52,43 +154,36 @@ impl INode3D for Boid3D {
             return;
         };
         let mut flock = flock.bind_mut();
-        flock.register_boid(self.get_id());
+        flock.register_boid(self.get_id(), self.vel, self.props, self.get_orientation());
         self.flock_id = Some(flock.get_id());
     }
 
-    fn ready(&mut self) {
-        if let Some(props) = self.properties.as_ref() {
-            self.props = props.bind().clone();
-        }
-    }
-
     fn exit_tree(&mut self) {
-        let mut flock: Gd<Flock3D> = Gd::from_instance_id(self.get_flock_id());
-        flock.bind_mut().unregister_boid(self.get_id());
+        let Some(mut flock) = self.get_flock() else { return; };
+        // Keep the simulated velocity, in case the boid enters a flock again
+        let boid = flock.bind_mut().unregister_boid(self.get_id());
+        if let Some(boid) = boid {
+            self.vel = boid.velocity;
+        }
     }
 }
 
 impl Boid for Boid3D {
-    #[inline(always)]
-    fn apply_force(&mut self, force: Vec3) {
-        self.vel += force;
-        self.vel = self.vel.clamp_length_max(self.props.max_speed);
-        let force_to_apply = Vector3::new(self.vel.x, self.vel.y, self.vel.z);
-        self.base_mut().translate(force_to_apply);
-    }
-
     #[inline(always)]
     fn get_boid_position(&self) -> Vec3 {
-        to_glam_vec(self.base().get_position())
+        to_glam_vec(self.base().get_global_position())
     }
 
     #[inline(always)]
     fn get_boid_velocity(&self) -> Vec3 {
-        self.vel
+        let id = self.get_id();
+        self.get_flock()
+            .and_then(|flock| flock.bind().boids.get(id).map(|boid| boid.velocity))
+            .unwrap_or(self.vel)
     }
 
     #[inline(always)]
-    fn get_boid_properties(&self) -> &BoidProperties {
+    fn get_boid_properties(&self) -> &BoidParams {
         &self.props
     }
 
@@ -105,27 +200,42 @@ use crate::flock::Flock;
 #[class(init, base=Node3D)]
 pub struct Flock3D {
     #[export]
+    #[var(get, set = set_properties)]
     properties: Option<Gd<FlockProperties>>,
-    props: FlockProperties,
+    props: FlockParams,
     #[export]
     target: Option<Gd<Node3D>>,
     #[export]
+    /// How boids of this flock react to boids of other flocks. Flocks without a relation are ignored.
+    relations: Array<Gd<FlockRelation>>,
+    #[export]
+    /// Spatial structure used to find neighbouring boids of this flock.
+    spatial_index: FlockSpatialIndex,
+    #[export]
+    /// Volume the boids of this flock are kept in, boids roam freely without one.
+    bounds: Option<Gd<FlockBounds>>,
+    #[export]
+    /// Boids wandering with the same seed take the same paths, as long as the flock keeps its boids.
+    wander_seed: u32,
+    #[export]
     #[init(val = true)]
     boid_processing_enabled: bool,
-    pub boids: FxIndexMap<InstanceId, Gd<Boid3D>>,
+    pub boids: BoidStore<Gd<Node3D>>,
+    // Global transform the boid nodes were last synced under, they move along with the flock node
+    synced_transform: Option<Transform3D>,
     base: Base<Node3D>,
 }
 
 impl Flock3D {
-    pub fn register_boid(&mut self, boid_id: InstanceId) {
+    pub fn register_boid(&mut self, boid_id: InstanceId, velocity: Vec3, properties: BoidParams, orientation: Option<BoidOrientation>) {
         let boid: Gd<Boid3D> = Gd::from_instance_id(boid_id);
-        self.boids.insert(boid_id, boid.clone());
+        self.boids.insert(boid_id, boid.clone().upcast(), velocity, properties, orientation);
         get_singleton().bind_mut().register_boid_3d(boid_id, boid);
     }
 
-    pub fn unregister_boid(&mut self, boid_id: InstanceId) {
-        self.boids.shift_remove(&boid_id);
+    pub fn unregister_boid(&mut self, boid_id: InstanceId) -> Option<BoidInstance> {
         get_singleton().bind_mut().unregister_boid_3d(boid_id);
+        self.boids.remove(boid_id)
     }
 }
 
@@ -135,12 +245,6 @@ impl INode3D for Flock3D {
         get_singleton().bind_mut().register_flock_3d(self.get_id())
     }
 
-    fn ready(&mut self) {
-        if let Some(props) = self.properties.as_ref() {
-            self.props = props.bind().clone();
-        }
-    }
-
     fn exit_tree(&mut self) {
         get_singleton().bind_mut().unregister_flock_3d(self.get_id())
     }
@@ -152,39 +256,68 @@ impl Flock3D {
     pub fn get_id(&self) -> InstanceId {
         self.base().instance_id()
     }
+
+    #[func]
+    fn set_properties(&mut self, properties: Option<Gd<FlockProperties>>) {
+        let on_changed = self.base().callable("on_properties_changed");
+        swap_properties(&mut self.properties, properties, &on_changed);
+        self.on_properties_changed();
+    }
+
+    #[func]
+    fn on_properties_changed(&mut self) {
+        self.props = self.properties.as_ref().map(|props| (&*props.bind()).into()).unwrap_or_default();
+    }
 }
 
 impl Flock for Flock3D {
-    fn get_flock_properties(&self) -> &FlockProperties {
+    fn get_flock_properties(&self) -> &FlockParams {
         &self.props
     }
 
     fn get_target_position(&self) -> Option<Vec3> {
-        self.target.as_ref().map(|t| to_glam_vec(t.get_position()))
-    }
-
-    fn get_boids_posvel(&self) -> Vec<(Vec3, Vec3)> {
-        let boid_count = self.boids.len();
-        let mut result = Vec::with_capacity(boid_count);
-        result.extend(self.boids.values().map(|b| {
-            let b = b.bind();
-            (b.get_boid_position(), b.get_boid_velocity())
-        }));
-        result
-    }
-
-    fn get_boids(&self) -> impl Iterator<Item = (&InstanceId, (Vec3, Vec3, BoidProperties))> {
-        self.boids.iter().map(|(id, boid)| {
-            let boid = boid.bind();
-            (
-                id,
-                (
-                    boid.get_boid_position(),
-                    boid.get_boid_velocity(),
-                    boid.get_boid_properties().clone(),
-                ),
-            )
-        })
+        self.target.as_ref().map(|t| to_glam_vec(t.get_global_position()))
+    }
+
+    fn get_boids(&self) -> &[BoidInstance] {
+        self.boids.boids()
+    }
+
+    fn pull_boid_teleports(&mut self) {
+        let transform = self.base().get_global_transform();
+        if self.synced_transform.replace(transform) != Some(transform) {
+            self.boids.pull_positions();
+        }
+    }
+
+    fn step_boids(&mut self, processed: &[BoidInstance], delta: f32) {
+        let bounds = self.get_flock_bounds();
+        self.boids.step(processed, delta, bounds.as_ref());
+    }
+    
+    fn get_flock_node(&self) -> Gd<Node> {
+        self.to_gd().upcast()
+    }
+
+    fn get_relations(&self) -> &Array<Gd<FlockRelation>> {
+        &self.relations
+    }
+
+    fn get_spatial_index_kind(&self) -> SpatialIndexKind {
+        self.spatial_index.into()
+    }
+
+    fn get_flock_bounds(&self) -> Option<BoundsInstance> {
+        let transform = to_glam_affine_3d(self.base().get_global_transform());
+        self.bounds.as_ref().map(|bounds| bounds.bind().to_bounds_instance(transform, false))
+    }
+
+    fn get_wander_seed(&self) -> u32 {
+        self.wander_seed
+    }
+
+    fn get_wander_time(&self) -> f64 {
+        self.boids.time()
     }
 
     fn is_boid_processing(&self) -> bool {
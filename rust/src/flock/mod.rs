@@ -1,12 +1,41 @@
+use boids_core::{BoidInstance, BoundsInstance, FlockParams, SpatialIndexKind};
 use glam::*;
 use godot::prelude::*;
-use crate::BoidProperties;
+use crate::{FlockRelation, FlockRelationKind};
+
+pub mod store;
+
+pub use store::*;
 
 // Flock trait - kept minimal for performance
 pub trait Flock {
-    fn get_flock_properties(&self) -> &crate::FlockProperties;
+    fn get_flock_properties(&self) -> &FlockParams;
+    /// Global position of the target.
     fn get_target_position(&self) -> Option<Vec3>;
-    fn get_boids(&self) -> impl Iterator<Item = (&InstanceId, (Vec3, Vec3, BoidProperties))>;
-    fn get_boids_posvel(&self) -> Vec<(Vec3, Vec3)>;
+    /// Simulated state of the flock's boids, positions are global.
+    fn get_boids(&self) -> &[BoidInstance];
+    /// Picks up boids moved along with the flock node since the last tick.
+    fn pull_boid_teleports(&mut self);
+    /// Integrates the forces and speed boosts of `processed` (one per boid, in [`Self::get_boids`] order) and syncs the boid nodes.
+    fn step_boids(&mut self, processed: &[BoidInstance], delta: f32);
+    /// Node the flock paths of relations are relative to.
+    fn get_flock_node(&self) -> Gd<Node>;
+    fn get_relations(&self) -> &Array<Gd<FlockRelation>>;
+    /// Relations of this flock as (other flock, kind, distance squared, weight). Relations to missing flocks are skipped.
+    fn get_flock_relations(&self) -> impl Iterator<Item = (InstanceId, FlockRelationKind, f32, f32)> {
+        let node = self.get_flock_node();
+        self.get_relations().iter_shared().filter_map(move |relation| {
+            let relation = relation.bind();
+            let flock = node.get_node_or_null(&relation.flock)?;
+            Some((flock.instance_id(), relation.kind, relation.distance, relation.weight))
+        })
+    }
+    fn get_spatial_index_kind(&self) -> SpatialIndexKind;
+    /// Volume the flock's boids are kept in, in global space.
+    fn get_flock_bounds(&self) -> Option<BoundsInstance>;
+    /// Seed of the noise the flock's boids wander along.
+    fn get_wander_seed(&self) -> u32;
+    /// Seconds the flock's boids have been stepped for, they wander along the noise as it passes.
+    fn get_wander_time(&self) -> f64;
     fn is_boid_processing(&self) -> bool;
 }
\ No newline at end of file
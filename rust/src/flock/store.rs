@@ -0,0 +1,231 @@
+use boids_core::{confine_boids, integrate_boids, BoidInstance, BoidParams, BoundsInstance};
+use glam::*;
+use godot::prelude::*;
+use rustc_hash::FxHashMap;
+
+use crate::{to_glam_affine_3d, BoidOrientation};
+
+// Node a boid's simulated position is mirrored to, in global space
+pub trait BoidNode {
+    fn read_position(&self) -> Vec3;
+    fn write_position(&mut self, position: Vec3);
+    // Writes the boid's position and turns the node to face along its velocity
+    fn write_oriented(&mut self, boid: &BoidInstance, orientation: &BoidOrientation, delta: f32);
+}
+
+impl BoidNode for Gd<Node2D> {
+    #[inline(always)]
+    fn read_position(&self) -> Vec3 {
+        let pos = self.get_global_position();
+        vec3(pos.x, pos.y, 0.0)
+    }
+
+    #[inline(always)]
+    fn write_position(&mut self, position: Vec3) {
+        self.set_global_position(Vector2::new(position.x, position.y));
+    }
+
+    fn write_oriented(&mut self, boid: &BoidInstance, orientation: &BoidOrientation, delta: f32) {
+        let rotation = orientation.turn_2d(self.get_global_rotation(), boid, delta);
+        self.set_global_position(Vector2::new(boid.position.x, boid.position.y));
+        self.set_global_rotation(rotation);
+    }
+}
+
+impl BoidNode for Gd<Node3D> {
+    #[inline(always)]
+    fn read_position(&self) -> Vec3 {
+        crate::to_glam_vec(self.get_global_position())
+    }
+
+    #[inline(always)]
+    fn write_position(&mut self, position: Vec3) {
+        self.set_global_position(Vector3::new(position.x, position.y, position.z));
+    }
+
+    fn write_oriented(&mut self, boid: &BoidInstance, orientation: &BoidOrientation, delta: f32) {
+        // Only the rotation is replaced, the node keeps its scale
+        let (scale, current, _) = to_glam_affine_3d(self.get_global_transform()).to_scale_rotation_translation();
+        let basis = Mat3::from_quat(orientation.turn_3d(current, boid, delta)) * Mat3::from_diagonal(scale);
+        let [a, b, c, origin] = [basis.x_axis, basis.y_axis, basis.z_axis, boid.position].map(|v| Vector3::new(v.x, v.y, v.z));
+        self.set_global_transform(Transform3D::from_cols(a, b, c, origin));
+    }
+}
+
+/// Authoritative state of a flock's boids, kept in Rust between ticks. Positions are global.
+///
+/// Nodes are only touched at the sync boundary: simulated positions are written out in [`Self::step`].
+/// Boids are moved outside of the simulation through [`Self::teleport`], or all at once in [`Self::pull_positions`]
+/// after the flock node moved them along.
+///
+/// Boids are either looked up by id after [`Self::insert`], or only kept in order after [`Self::push`].
+pub struct BoidStore<N: BoidNode> {
+    // `None` for boids that were pushed without an id
+    ids: Vec<Option<InstanceId>>,
+    nodes: Vec<N>,
+    boids: Vec<BoidInstance>,
+    // Boids without an orientation only have their position written
+    orientations: Vec<Option<BoidOrientation>>,
+    indices: FxHashMap<InstanceId, usize>,
+    // Id the next inserted boid gets, ids aren't reused so every boid keeps its spot in the wander noise
+    next_id: u32,
+    // Seconds stepped so far, the flock's boids wander along the noise as it passes
+    time: f64,
+}
+
+impl<N: BoidNode> Default for BoidStore<N> {
+    fn default() -> Self {
+        Self {
+            ids: Vec::new(),
+            nodes: Vec::new(),
+            boids: Vec::new(),
+            orientations: Vec::new(),
+            indices: FxHashMap::default(),
+            next_id: 0,
+            time: 0.0,
+        }
+    }
+}
+
+impl<N: BoidNode> BoidStore<N> {
+    pub fn insert(&mut self, id: InstanceId, node: N, velocity: Vec3, properties: BoidParams, orientation: Option<BoidOrientation>) {
+        let Some(&idx) = self.indices.get(&id) else {
+            self.indices.insert(id, self.ids.len());
+            self.push_with_id(Some(id), node, velocity, properties, orientation);
+            return;
+        };
+        let position = node.read_position();
+        self.nodes[idx] = node;
+        self.boids[idx] = BoidInstance { id: self.boids[idx].id, ..BoidInstance::new(position, velocity, properties, 0) };
+        self.orientations[idx] = orientation;
+    }
+
+    /// Adds a boid that is only kept in order, it can't be looked up or removed by id.
+    #[inline(always)]
+    pub fn push(&mut self, node: N, velocity: Vec3, properties: BoidParams, orientation: Option<BoidOrientation>) {
+        self.push_with_id(None, node, velocity, properties, orientation);
+    }
+
+    fn push_with_id(&mut self, id: Option<InstanceId>, node: N, velocity: Vec3, properties: BoidParams, orientation: Option<BoidOrientation>) {
+        let position = node.read_position();
+        self.ids.push(id);
+        self.nodes.push(node);
+        self.boids.push(BoidInstance { id: self.next_id, ..BoidInstance::new(position, velocity, properties, 0) });
+        self.next_id = self.next_id.wrapping_add(1);
+        self.orientations.push(orientation);
+    }
+
+    /// Removes a boid, returning its last simulated state.
+    pub fn remove(&mut self, id: InstanceId) -> Option<BoidInstance> {
+        let idx = self.indices.remove(&id)?;
+        self.ids.swap_remove(idx);
+        self.nodes.swap_remove(idx);
+        self.orientations.swap_remove(idx);
+        let boid = self.boids.swap_remove(idx);
+        if let Some(&Some(moved)) = self.ids.get(idx) {
+            self.indices.insert(moved, idx);
+        }
+        Some(boid)
+    }
+
+    /// Removes every boid past the first `len`.
+    pub fn truncate(&mut self, len: usize) {
+        for id in self.ids.drain(len.min(self.ids.len())..).flatten() {
+            self.indices.remove(&id);
+        }
+        self.nodes.truncate(len);
+        self.boids.truncate(len);
+        self.orientations.truncate(len);
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    #[inline(always)]
+    pub fn nodes(&self) -> &[N] {
+        &self.nodes
+    }
+
+    #[inline(always)]
+    pub fn boids(&self) -> &[BoidInstance] {
+        &self.boids
+    }
+
+    #[inline(always)]
+    pub fn boids_mut(&mut self) -> &mut [BoidInstance] {
+        &mut self.boids
+    }
+
+    /// Seconds the store has been stepped for.
+    #[inline(always)]
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    #[inline(always)]
+    pub fn get(&self, id: InstanceId) -> Option<&BoidInstance> {
+        self.indices.get(&id).map(|&idx| &self.boids[idx])
+    }
+
+    #[inline(always)]
+    pub fn get_mut(&mut self, id: InstanceId) -> Option<&mut BoidInstance> {
+        self.indices.get(&id).map(|&idx| &mut self.boids[idx])
+    }
+
+    /// Sets how a boid turns along its velocity, `None` leaves the node's rotation alone.
+    pub fn set_orientation(&mut self, id: InstanceId, orientation: Option<BoidOrientation>) {
+        if let Some(&idx) = self.indices.get(&id) {
+            self.orientations[idx] = orientation;
+        }
+    }
+
+    /// Sets how every boid turns along its velocity.
+    pub fn set_orientations(&mut self, orientation: Option<BoidOrientation>) {
+        self.orientations.fill(orientation);
+    }
+
+    /// Moves a boid to `position` outside of the simulation, its node is expected to be moved there already.
+    pub fn teleport(&mut self, id: InstanceId, position: Vec3) {
+        if let Some(boid) = self.get_mut(id) {
+            boid.position = position;
+        }
+    }
+
+    /// Adopts the node position of every boid, reading each node.
+    /// Only needed once the nodes were moved along with their parent, single boids are moved through [`Self::teleport`].
+    pub fn pull_positions(&mut self) {
+        for (boid, node) in self.boids.iter_mut().zip(&self.nodes) {
+            boid.position = node.read_position();
+        }
+    }
+
+    /// Integrates the forces and speed boosts of `processed` (one per boid, in store order) over `delta` seconds
+    /// and writes the results to the nodes.
+    /// Nodes of boids with an orientation are also turned along their velocity.
+    /// Boids that left `bounds` are wrapped or bounced before being written.
+    pub fn step(&mut self, processed: &[BoidInstance], delta: f32, bounds: Option<&BoundsInstance>) {
+        for (boid, processed) in self.boids.iter_mut().zip(processed) {
+            boid.force = processed.force;
+            boid.speed_boost = processed.speed_boost;
+        }
+        integrate_boids(&mut self.boids, delta);
+        if let Some(bounds) = bounds {
+            confine_boids(&mut self.boids, bounds);
+        }
+        self.time += delta as f64;
+
+        for (boid, (node, orientation)) in self.boids.iter().zip(self.nodes.iter_mut().zip(&self.orientations)) {
+            match orientation {
+                Some(orientation) => node.write_oriented(boid, orientation, delta),
+                None => node.write_position(boid.position),
+            }
+        }
+    }
+}
@@ -1,15 +1,26 @@
+
+use boids_core::*;
 use glam::*;
-use godot::{classes::Engine, prelude::*};
+use godot::{
+    classes::Engine,
+    obj::{bounds::DeclUser, Bounds},
+    prelude::*,
+};
 use indexmap::IndexMap;
-use rustc_hash::FxBuildHasher;
+use rustc_hash::{FxBuildHasher, FxHashMap};
+use std::ops::Range;
 
-mod algorithms;
 mod boid;
 mod flock;
+mod obstacle;
+mod predator;
+mod swarm;
 
-pub use algorithms::*;
 pub use boid::*;
 pub use flock::*;
+pub use obstacle::*;
+pub use predator::*;
+pub use swarm::*;
 
 type FxIndexMap<K, V> = IndexMap<K, V, FxBuildHasher>;
 
@@ -27,12 +38,9 @@ struct BoidsExtension;
 #[gdextension]
 unsafe impl ExtensionLibrary for BoidsExtension {
     fn on_level_init(level: InitLevel) {
-        match level {
-            InitLevel::Scene => {
-                let singleton = Boids::new_alloc();
-                Engine::singleton().register_singleton(SINGLETON_NAME, &singleton);
-            }
-            _ => (),
+        if level == InitLevel::Scene {
+            let singleton = Boids::new_alloc();
+            Engine::singleton().register_singleton(SINGLETON_NAME, &singleton);
         }
     }
 
@@ -60,6 +68,16 @@ pub struct BoidsProcess {
     #[export]
     #[init(val = 1)]
     process_per_tick: i64,
+    #[export]
+    #[var(get, set = set_max_boids_2d)]
+    /// Maximum amount of 2D boids simulated, 0 means unlimited.
+    max_boids_2d: i64,
+    #[export]
+    #[var(get, set = set_max_boids_3d)]
+    /// Maximum amount of 3D boids simulated, 0 means unlimited.
+    max_boids_3d: i64,
+    // Physics time accumulated since boids were last processed
+    elapsed: f64,
     boids: Option<Gd<Boids>>,
     engine: Option<Gd<Engine>>,
 }
@@ -67,23 +85,52 @@ pub struct BoidsProcess {
 #[godot_api]
 impl INode for BoidsProcess {
     fn ready(&mut self) {
-        self.boids = Some(get_singleton());
+        let mut boids = get_singleton();
+        {
+            let mut s = boids.bind_mut();
+            s.set_max_boid_2d_count(self.max_boids_2d);
+            s.set_max_boid_3d_count(self.max_boids_3d);
+        }
+        self.boids = Some(boids);
         self.engine = Some(Engine::singleton());
     }
 
-    fn physics_process(&mut self, _: f64) {
-        if self.engine.as_ref().unwrap().get_physics_frames() % (self.process_per_tick as u64) == 0 {
+    fn physics_process(&mut self, delta: f64) {
+        self.elapsed += delta;
+        if self.engine.as_ref().unwrap().get_physics_frames().is_multiple_of(self.process_per_tick as u64) {
+            // Integrate over every tick since the last processing, including skipped ones
+            let elapsed = std::mem::take(&mut self.elapsed);
             let mut s = self.boids.as_mut().unwrap().bind_mut();
             if self.process_2d {
-                s.process_boids_2d();
+                s.advance_boids_2d(elapsed);
             }
             if self.process_3d {
-                s.process_boids_3d();
+                s.advance_boids_3d(elapsed);
             }
         }
     }
 }
 
+#[godot_api]
+impl BoidsProcess {
+    #[func]
+    fn set_max_boids_2d(&mut self, max: i64) {
+        self.max_boids_2d = max;
+        // Before `ready` the limit is handed over there
+        if let Some(boids) = self.boids.as_mut() {
+            boids.bind_mut().set_max_boid_2d_count(max);
+        }
+    }
+
+    #[func]
+    fn set_max_boids_3d(&mut self, max: i64) {
+        self.max_boids_3d = max;
+        if let Some(boids) = self.boids.as_mut() {
+            boids.bind_mut().set_max_boid_3d_count(max);
+        }
+    }
+}
+
 #[derive(GodotClass)]
 #[class(init, base=Object)]
 struct Boids {
@@ -92,14 +139,26 @@ struct Boids {
     #[init(val = FxIndexMap::default())]
     boids2d: FxIndexMap<InstanceId, Gd<Boid2D>>,
     #[init(val = FxIndexMap::default())]
+    swarms2d: FxIndexMap<InstanceId, Gd<BoidSwarm2D>>,
+    #[init(val = FxIndexMap::default())]
+    obstacles2d: FxIndexMap<InstanceId, Gd<BoidObstacle2D>>,
+    #[init(val = FxIndexMap::default())]
+    predators2d: FxIndexMap<InstanceId, Gd<BoidPredator2D>>,
+    #[init(val = FxIndexMap::default())]
     flocks3d: FxIndexMap<InstanceId, Gd<Flock3D>>,
     #[init(val = FxIndexMap::default())]
     boids3d: FxIndexMap<InstanceId, Gd<Boid3D>>,
+    #[init(val = FxIndexMap::default())]
+    swarms3d: FxIndexMap<InstanceId, Gd<BoidSwarm3D>>,
+    #[init(val = FxIndexMap::default())]
+    obstacles3d: FxIndexMap<InstanceId, Gd<BoidObstacle3D>>,
+    #[init(val = FxIndexMap::default())]
+    predators3d: FxIndexMap<InstanceId, Gd<BoidPredator3D>>,
     
-    // Ultra-performance processors
-    #[init(val = UltraBoidProcessor::new(15000, 75.0))]
+    // Ultra-performance processors, buffers grow with the boid count and cells follow the flock radii
+    #[init(val = UltraBoidProcessor::new(1024, Dimensions::D2))]
     processor_2d: UltraBoidProcessor,
-    #[init(val = UltraBoidProcessor::new(15000, 50.0))]
+    #[init(val = UltraBoidProcessor::new(1024, Dimensions::D3))]
     processor_3d: UltraBoidProcessor,
     
     base: Base<Object>,
@@ -123,6 +182,37 @@ impl Boids {
         self.boids2d.shift_remove(&boid_id);
     }
 
+    fn register_swarm_2d(&mut self, swarm_id: InstanceId) {
+        let swarm = Gd::from_instance_id(swarm_id);
+        self.swarms2d.insert(swarm_id, swarm);
+    }
+
+    fn unregister_swarm_2d(&mut self, swarm_id: InstanceId) {
+        self.swarms2d.shift_remove(&swarm_id);
+    }
+
+    fn swarm_boid_2d_count(&self) -> usize {
+        self.swarms2d.values().map(|swarm| swarm.bind().get_boids().len()).sum()
+    }
+
+    fn register_obstacle_2d(&mut self, obstacle_id: InstanceId) {
+        let obstacle = Gd::from_instance_id(obstacle_id);
+        self.obstacles2d.insert(obstacle_id, obstacle);
+    }
+
+    fn unregister_obstacle_2d(&mut self, obstacle_id: InstanceId) {
+        self.obstacles2d.shift_remove(&obstacle_id);
+    }
+
+    fn register_predator_2d(&mut self, predator_id: InstanceId) {
+        let predator = Gd::from_instance_id(predator_id);
+        self.predators2d.insert(predator_id, predator);
+    }
+
+    fn unregister_predator_2d(&mut self, predator_id: InstanceId) {
+        self.predators2d.shift_remove(&predator_id);
+    }
+
     fn register_flock_3d(&mut self, flock_id: InstanceId) {
         let flock = Gd::from_instance_id(flock_id);
         self.flocks3d.insert(flock_id, flock);
@@ -139,23 +229,75 @@ impl Boids {
     fn unregister_boid_3d(&mut self, boid_id: InstanceId) {
         self.boids3d.shift_remove(&boid_id);
     }
+
+    fn register_swarm_3d(&mut self, swarm_id: InstanceId) {
+        let swarm = Gd::from_instance_id(swarm_id);
+        self.swarms3d.insert(swarm_id, swarm);
+    }
+
+    fn unregister_swarm_3d(&mut self, swarm_id: InstanceId) {
+        self.swarms3d.shift_remove(&swarm_id);
+    }
+
+    fn swarm_boid_3d_count(&self) -> usize {
+        self.swarms3d.values().map(|swarm| swarm.bind().get_boids().len()).sum()
+    }
+
+    fn register_obstacle_3d(&mut self, obstacle_id: InstanceId) {
+        let obstacle = Gd::from_instance_id(obstacle_id);
+        self.obstacles3d.insert(obstacle_id, obstacle);
+    }
+
+    fn unregister_obstacle_3d(&mut self, obstacle_id: InstanceId) {
+        self.obstacles3d.shift_remove(&obstacle_id);
+    }
+
+    fn register_predator_3d(&mut self, predator_id: InstanceId) {
+        let predator = Gd::from_instance_id(predator_id);
+        self.predators3d.insert(predator_id, predator);
+    }
+
+    fn unregister_predator_3d(&mut self, predator_id: InstanceId) {
+        self.predators3d.shift_remove(&predator_id);
+    }
 }
 
 #[godot_api]
 impl Boids {
+    /// Simulates all 2D flocks over one physics tick.
     #[func]
     fn process_boids_2d(&mut self) {
-        process_boids_ultra_2d(&mut self.boids2d, &self.flocks2d, &mut self.processor_2d);
+        self.advance_boids_2d(physics_tick());
     }
 
+    /// Simulates all 3D flocks over one physics tick.
     #[func]
     fn process_boids_3d(&mut self) {
-        process_boids_ultra_3d(&mut self.boids3d, &self.flocks3d, &mut self.processor_3d);
+        self.advance_boids_3d(physics_tick());
+    }
+
+    /// Simulates all 2D flocks over `delta` seconds.
+    #[func]
+    fn advance_boids_2d(&mut self, delta: f64) {
+        let boid_count = self.boids2d.len() + self.swarm_boid_2d_count();
+        self.processor_2d.load_obstacles(self.obstacles2d.values_mut().filter_map(|obstacle| obstacle.bind_mut().get_obstacle_instance()));
+        self.processor_2d.load_predators(self.predators2d.values().filter_map(|predator| predator.bind().get_predator_instance()));
+        process_boids_ultra_2d(&self.flocks2d, &self.swarms2d, boid_count, &mut self.processor_2d, delta as f32);
     }
 
+    /// Simulates all 3D flocks over `delta` seconds.
+    #[func]
+    fn advance_boids_3d(&mut self, delta: f64) {
+        let boid_count = self.boids3d.len() + self.swarm_boid_3d_count();
+        self.processor_3d.load_obstacles(self.obstacles3d.values_mut().filter_map(|obstacle| obstacle.bind_mut().get_obstacle_instance()));
+        self.processor_3d.load_predators(self.predators3d.values().filter_map(|predator| predator.bind().get_predator_instance()));
+        process_boids_ultra_3d(&self.flocks3d, &self.swarms3d, boid_count, &mut self.processor_3d, delta as f32);
+    }
+
+    /// Amount of 2D boids, including the boids of every `BoidSwarm2D`.
     #[func]
     fn get_total_boid_2d_count(&self) -> i64 {
-        self.boids2d.len() as i64
+        (self.boids2d.len() + self.swarm_boid_2d_count()) as i64
     }
 
     #[func]
@@ -163,15 +305,62 @@ impl Boids {
         self.flocks2d.len() as i64
     }
 
+    /// Amount of 3D boids, including the boids of every `BoidSwarm3D`.
     #[func]
     fn get_total_boid_3d_count(&self) -> i64 {
-        self.boids3d.len() as i64
+        (self.boids3d.len() + self.swarm_boid_3d_count()) as i64
     }
 
     #[func]
     fn get_total_flock_3d_count(&self) -> i64 {
         self.flocks3d.len() as i64
     }
+
+    /// Amount of 2D boids the processor currently has room for without growing.
+    #[func]
+    fn get_boid_2d_capacity(&self) -> i64 {
+        self.processor_2d.capacity() as i64
+    }
+
+    /// Amount of 3D boids the processor currently has room for without growing.
+    #[func]
+    fn get_boid_3d_capacity(&self) -> i64 {
+        self.processor_3d.capacity() as i64
+    }
+
+    #[func]
+    fn get_max_boid_2d_count(&self) -> i64 {
+        self.processor_2d.max_capacity().unwrap_or(0) as i64
+    }
+
+    /// Limits how many 2D boids get simulated, 0 means unlimited.
+    #[func]
+    fn set_max_boid_2d_count(&mut self, max: i64) {
+        self.processor_2d.set_max_capacity((max > 0).then_some(max as usize));
+    }
+
+    #[func]
+    fn get_max_boid_3d_count(&self) -> i64 {
+        self.processor_3d.max_capacity().unwrap_or(0) as i64
+    }
+
+    /// Limits how many 3D boids get simulated, 0 means unlimited.
+    #[func]
+    fn set_max_boid_3d_count(&mut self, max: i64) {
+        self.processor_3d.set_max_capacity((max > 0).then_some(max as usize));
+    }
+
+    /// Amount of 2D boids left out of the last tick because the limit was hit.
+    #[func]
+    fn get_dropped_boid_2d_count(&self) -> i64 {
+        self.processor_2d.dropped_count() as i64
+    }
+
+    /// Amount of 3D boids left out of the last tick because the limit was hit.
+    #[func]
+    fn get_dropped_boid_3d_count(&self) -> i64 {
+        self.processor_3d.dropped_count() as i64
+    }
 }
 
 #[inline(always)]
@@ -179,88 +368,173 @@ const fn to_glam_vec(godot_vec: Vector3) -> Vec3 {
     vec3(godot_vec.x, godot_vec.y, godot_vec.z)
 }
 
+#[inline(always)]
+fn to_glam_affine_2d(transform: Transform2D) -> Affine2 {
+    Affine2::from_cols(
+        vec2(transform.a.x, transform.a.y),
+        vec2(transform.b.x, transform.b.y),
+        vec2(transform.origin.x, transform.origin.y),
+    )
+}
+
+// 2D transform acting on the xy plane of the 3D simulation space
+#[inline(always)]
+fn to_glam_affine_2d_in_3d(transform: Transform2D) -> Affine3A {
+    let transform = to_glam_affine_2d(transform);
+    Affine3A::from_cols(
+        transform.matrix2.x_axis.extend(0.0).into(),
+        transform.matrix2.y_axis.extend(0.0).into(),
+        Vec3A::Z,
+        transform.translation.extend(0.0).into(),
+    )
+}
+
+#[inline(always)]
+fn to_glam_affine_3d(transform: Transform3D) -> Affine3A {
+    let [a, b, c] = transform.basis.to_cols();
+    Affine3A::from_cols(
+        to_glam_vec(a).into(),
+        to_glam_vec(b).into(),
+        to_glam_vec(c).into(),
+        to_glam_vec(transform.origin).into(),
+    )
+}
+
+fn warn_dropped(kind: &str, dropped_before: usize, processor: &UltraBoidProcessor) {
+    let dropped = processor.dropped_count();
+    if dropped > 0 && dropped_before == 0 {
+        let max = processor.max_capacity().unwrap_or(processor.capacity());
+        godot_warn!("[Boids] {kind} boid limit of {max} reached, {dropped} boids are not being simulated");
+    }
+}
+
+// Processing flocks of one type, with the slice of the tick's boids each of them owns
+type FlockRanges<F> = Vec<(Gd<F>, Range<usize>)>;
+
+// Assigns every processing flock its index into the tick's flock instances, so relations can be resolved
+fn index_flocks<F>(flocks: &FxIndexMap<InstanceId, Gd<F>>, flock_indices: &mut FxHashMap<InstanceId, u32>)
+where
+    F: Flock + GodotClass + Bounds<Declarer = DeclUser>,
+{
+    for (id, flock) in flocks.iter() {
+        if flock.bind().is_boid_processing() {
+            let idx = flock_indices.len() as u32;
+            flock_indices.insert(*id, idx);
+        }
+    }
+}
+
+// Adds every processing flock and its boids to the tick's buffers
+fn gather_flocks<F>(
+    flocks: &FxIndexMap<InstanceId, Gd<F>>,
+    flock_indices: &FxHashMap<InstanceId, u32>,
+    flock_instances: &mut Vec<FlockInstance>,
+    boid_instances: &mut Vec<BoidInstance>,
+) -> FlockRanges<F>
+where
+    F: Flock + GodotClass + Bounds<Declarer = DeclUser>,
+{
+    let mut flock_ranges = Vec::with_capacity(flocks.len());
+    for (_, flock_gd) in flocks.iter() {
+        let mut handle = flock_gd.clone();
+        let mut flock = handle.bind_mut();
+        if !flock.is_boid_processing() { continue; }
+        flock.pull_boid_teleports();
+        
+        // Every flock is simulated with its own properties and target
+        let flock_idx = flock_instances.len() as u32;
+        let mut flock_instance = FlockInstance::new(
+            *flock.get_flock_properties(),
+            flock.get_target_position(),
+        );
+        flock_instance.spatial_index = flock.get_spatial_index_kind();
+        flock_instance.bounds = flock.get_flock_bounds();
+        flock_instance.seed = flock.get_wander_seed();
+        flock_instance.time = flock.get_wander_time();
+        flock_instance.relations.extend(flock.get_flock_relations().filter_map(|(other, kind, distance, weight)| {
+            Some(FlockRelationInstance { flock: *flock_indices.get(&other)?, kind: kind.into(), distance, weight })
+        }));
+        flock_instances.push(flock_instance);
+        
+        // Boid state lives on the Rust side, no boid node is touched here
+        let start = boid_instances.len();
+        boid_instances.extend(flock.get_boids().iter().map(|boid| BoidInstance { flock: flock_idx, ..*boid }));
+        flock_ranges.push((flock_gd.clone(), start..boid_instances.len()));
+    }
+    flock_ranges
+}
+
+// Integrates on the Rust side, then lets every flock write its boids out
+fn step_flocks<F>(flock_ranges: FlockRanges<F>, boid_instances: &[BoidInstance], delta: f32)
+where
+    F: Flock + GodotClass + Bounds<Declarer = DeclUser>,
+{
+    for (mut flock, range) in flock_ranges {
+        flock.bind_mut().step_boids(&boid_instances[range], delta);
+    }
+}
+
+// Seconds per physics tick
+fn physics_tick() -> f64 {
+    1.0 / Engine::singleton().get_physics_ticks_per_second().max(1) as f64
+}
+
 // Ultra-performance processing functions
 fn process_boids_ultra_2d(
-    boids: &mut FxIndexMap<InstanceId, Gd<Boid2D>>,
     flocks: &FxIndexMap<InstanceId, Gd<Flock2D>>,
+    swarms: &FxIndexMap<InstanceId, Gd<BoidSwarm2D>>,
+    boid_count: usize,
     processor: &mut UltraBoidProcessor,
+    delta: f32,
 ) {
-    if flocks.is_empty() { return; }
+    if delta <= 0.0 { return; }
     
     // Collect all boids into algorithm-friendly format
-    let mut boid_instances = Vec::with_capacity(boids.len());
-    let mut boid_ids = Vec::with_capacity(boids.len());
-    let mut flock_props = None;
-    let mut target_pos = None;
+    let mut boid_instances = Vec::with_capacity(boid_count);
+    let mut flock_instances = Vec::with_capacity(flocks.len() + swarms.len());
     
-    for (_, flock) in flocks.iter() {
-        let flock = flock.bind();
-        if !flock.is_boid_processing() { continue; }
-        
-        // For simplicity, use first flock's properties
-        // (you can extend this for multiple flocks)
-        if flock_props.is_none() {
-            flock_props = Some(flock.get_flock_properties().clone());
-            target_pos = flock.get_target_position();
-        }
-        
-        for (boid_id, (pos, vel, props)) in flock.get_boids() {
-            boid_instances.push(BoidInstance::new(pos, vel, props));
-            boid_ids.push(*boid_id);
-        }
-    }
+    // Relations are resolved to indices into `flock_instances`, flocks and swarms can relate to each other
+    let mut flock_indices = FxHashMap::default();
+    index_flocks(flocks, &mut flock_indices);
+    index_flocks(swarms, &mut flock_indices);
+    let flock_ranges = gather_flocks(flocks, &flock_indices, &mut flock_instances, &mut boid_instances);
+    let swarm_ranges = gather_flocks(swarms, &flock_indices, &mut flock_instances, &mut boid_instances);
     
+    // Process with ultra-performance algorithm, without boids this only clears the dropped count
+    let dropped_before = processor.dropped_count();
+    processor.process_boids(&mut boid_instances, &flock_instances, delta);
+    warn_dropped("2D", dropped_before, processor);
     if boid_instances.is_empty() { return; }
     
-    // Process with ultra-performance algorithm
-    if let Some(props) = flock_props {
-        processor.process_boids(&mut boid_instances, &props, target_pos);
-    }
-    
-    // Apply forces back to Godot objects
-    for (i, boid_id) in boid_ids.iter().enumerate() {
-        if let Some(boid) = boids.get_mut(boid_id) {
-            boid.bind_mut().apply_force(boid_instances[i].force);
-        }
-    }
+    step_flocks(flock_ranges, &boid_instances, delta);
+    step_flocks(swarm_ranges, &boid_instances, delta);
 }
 
 fn process_boids_ultra_3d(
-    boids: &mut FxIndexMap<InstanceId, Gd<Boid3D>>,
     flocks: &FxIndexMap<InstanceId, Gd<Flock3D>>,
+    swarms: &FxIndexMap<InstanceId, Gd<BoidSwarm3D>>,
+    boid_count: usize,
     processor: &mut UltraBoidProcessor,
+    delta: f32,
 ) {
-    if flocks.is_empty() { return; }
+    if delta <= 0.0 { return; }
     
-    let mut boid_instances = Vec::with_capacity(boids.len());
-    let mut boid_ids = Vec::with_capacity(boids.len());
-    let mut flock_props = None;
-    let mut target_pos = None;
+    let mut boid_instances = Vec::with_capacity(boid_count);
+    let mut flock_instances = Vec::with_capacity(flocks.len() + swarms.len());
     
-    for (_, flock) in flocks.iter() {
-        let flock = flock.bind();
-        if !flock.is_boid_processing() { continue; }
-        
-        if flock_props.is_none() {
-            flock_props = Some(flock.get_flock_properties().clone());
-            target_pos = flock.get_target_position();
-        }
-        
-        for (boid_id, (pos, vel, props)) in flock.get_boids() {
-            boid_instances.push(BoidInstance::new(pos, vel, props));
-            boid_ids.push(*boid_id);
-        }
-    }
+    // Flocks and swarms share one simulation, so they can relate to each other
+    let mut flock_indices = FxHashMap::default();
+    index_flocks(flocks, &mut flock_indices);
+    index_flocks(swarms, &mut flock_indices);
+    let flock_ranges = gather_flocks(flocks, &flock_indices, &mut flock_instances, &mut boid_instances);
+    let swarm_ranges = gather_flocks(swarms, &flock_indices, &mut flock_instances, &mut boid_instances);
     
+    // Without boids this only clears the dropped count
+    let dropped_before = processor.dropped_count();
+    processor.process_boids(&mut boid_instances, &flock_instances, delta);
+    warn_dropped("3D", dropped_before, processor);
     if boid_instances.is_empty() { return; }
     
-    if let Some(props) = flock_props {
-        processor.process_boids(&mut boid_instances, &props, target_pos);
-    }
-    
-    for (i, boid_id) in boid_ids.iter().enumerate() {
-        if let Some(boid) = boids.get_mut(boid_id) {
-            boid.bind_mut().apply_force(boid_instances[i].force);
-        }
-    }
-}
\ No newline at end of file
+    step_flocks(flock_ranges, &boid_instances, delta);
+    step_flocks(swarm_ranges, &boid_instances, delta);
+}
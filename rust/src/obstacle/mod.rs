@@ -0,0 +1,5 @@
+pub mod obstacle_2d;
+pub mod obstacle_3d;
+
+pub use obstacle_2d::*;
+pub use obstacle_3d::*;
@@ -0,0 +1,134 @@
+use boids_core::{ObstacleInstance, ObstacleShape};
+use glam::*;
+use godot::prelude::*;
+
+use crate::{get_singleton, to_glam_affine_2d_in_3d};
+
+/// Shape of a `BoidObstacle2D`.
+#[derive(GodotConvert, Var, Export, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[godot(via = i64)]
+pub enum ObstacleShape2D {
+    /// Avoided within `margin` of the obstacle's position.
+    Point,
+    #[default]
+    Circle,
+    Rectangle,
+    Polygon,
+}
+
+#[derive(GodotClass)]
+#[class(init, base=Node2D)]
+/// Area 2D boids of every flock steer around, following the node's global transform.
+///
+/// Boids start steering away within `margin` of the shape, and look ahead along their velocity by up to `margin`
+/// so they turn before running into it. How hard they steer is set by `avoidance` in `BoidProperties`.
+pub struct BoidObstacle2D {
+    #[export]
+    #[var(get, set = set_shape)]
+    shape: ObstacleShape2D,
+    #[export]
+    #[var(get, set = set_radius)]
+    #[init(val = 32.0)]
+    /// Radius of the circle.
+    radius: f32,
+    #[export]
+    #[var(get, set = set_size)]
+    #[init(val = Vector2::new(64.0, 64.0))]
+    /// Size of the rectangle, centered on the node.
+    size: Vector2,
+    #[export]
+    #[var(get, set = set_polygon)]
+    /// Points of the polygon, relative to the node.
+    polygon: PackedVector2Array,
+    #[export]
+    #[var(get, set = set_margin)]
+    #[init(val = 48.0)]
+    /// Distance from the shape at which boids start steering away.
+    margin: f32,
+    #[export]
+    #[init(val = true)]
+    avoidance_enabled: bool,
+    // Last built instance and the global transform it was built for, cleared when the shape changes
+    cached: Option<(Transform2D, ObstacleInstance)>,
+    base: Base<Node2D>,
+}
+
+impl BoidObstacle2D {
+    /// The obstacle as seen by the simulation, `None` while disabled.
+    /// Only rebuilt once the shape or the node's transform changes.
+    pub fn get_obstacle_instance(&mut self) -> Option<ObstacleInstance> {
+        if !self.avoidance_enabled { return None; }
+        let transform = self.base().get_global_transform();
+        let shape = match &self.cached {
+            Some((cached_transform, instance)) if *cached_transform == transform => return Some(instance.clone()),
+            // Moving the node keeps the shape, polygons don't get copied again
+            Some((_, instance)) => instance.shape.clone(),
+            None => self.build_shape(),
+        };
+        let instance = ObstacleInstance::new(shape, to_glam_affine_2d_in_3d(transform), self.margin);
+        self.cached = Some((transform, instance.clone()));
+        Some(instance)
+    }
+
+    fn build_shape(&self) -> ObstacleShape {
+        match self.shape {
+            ObstacleShape2D::Point => ObstacleShape::Point,
+            ObstacleShape2D::Circle => ObstacleShape::Sphere { radius: self.radius },
+            ObstacleShape2D::Rectangle => ObstacleShape::Box {
+                half_extents: vec3(self.size.x.abs(), self.size.y.abs(), 0.0) * 0.5,
+            },
+            ObstacleShape2D::Polygon => ObstacleShape::Polygon {
+                points: self.polygon.as_slice().iter().map(|p| vec2(p.x, p.y)).collect(),
+            },
+        }
+    }
+}
+
+#[godot_api]
+impl INode2D for BoidObstacle2D {
+    fn enter_tree(&mut self) {
+        get_singleton().bind_mut().register_obstacle_2d(self.get_id())
+    }
+
+    fn exit_tree(&mut self) {
+        get_singleton().bind_mut().unregister_obstacle_2d(self.get_id())
+    }
+}
+
+#[godot_api]
+impl BoidObstacle2D {
+    #[func]
+    pub fn get_id(&self) -> InstanceId {
+        self.base().instance_id()
+    }
+
+    #[func]
+    fn set_shape(&mut self, value: ObstacleShape2D) {
+        self.shape = value;
+        self.cached = None;
+    }
+
+    #[func]
+    fn set_radius(&mut self, value: f32) {
+        self.radius = value;
+        self.cached = None;
+    }
+
+    #[func]
+    fn set_size(&mut self, value: Vector2) {
+        self.size = value;
+        self.cached = None;
+    }
+
+    #[func]
+    fn set_polygon(&mut self, value: PackedVector2Array) {
+        self.polygon = value;
+        self.cached = None;
+    }
+
+    #[func]
+    fn set_margin(&mut self, value: f32) {
+        self.margin = value;
+        self.cached = None;
+    }
+}
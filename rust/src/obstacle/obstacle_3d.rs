@@ -0,0 +1,133 @@
+use boids_core::{ObstacleInstance, ObstacleShape};
+use godot::prelude::*;
+
+use crate::{get_singleton, to_glam_affine_3d, to_glam_vec};
+
+/// Shape of a `BoidObstacle3D`.
+#[derive(GodotConvert, Var, Export, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[godot(via = i64)]
+pub enum ObstacleShape3D {
+    /// Avoided within `margin` of the obstacle's position.
+    Point,
+    #[default]
+    Sphere,
+    Box,
+    /// Capsule along the node's y axis.
+    Capsule,
+}
+
+#[derive(GodotClass)]
+#[class(init, base=Node3D)]
+/// Volume 3D boids of every flock steer around, following the node's global transform.
+///
+/// Boids start steering away within `margin` of the shape, and look ahead along their velocity by up to `margin`
+/// so they turn before running into it. How hard they steer is set by `avoidance` in `BoidProperties`.
+pub struct BoidObstacle3D {
+    #[export]
+    #[var(get, set = set_shape)]
+    shape: ObstacleShape3D,
+    #[export]
+    #[var(get, set = set_radius)]
+    #[init(val = 1.0)]
+    /// Radius of the sphere or capsule.
+    radius: f32,
+    #[export]
+    #[var(get, set = set_size)]
+    #[init(val = Vector3::new(2.0, 2.0, 2.0))]
+    /// Size of the box, centered on the node.
+    size: Vector3,
+    #[export]
+    #[var(get, set = set_height)]
+    #[init(val = 2.0)]
+    /// Full height of the capsule, including its rounded ends.
+    height: f32,
+    #[export]
+    #[var(get, set = set_margin)]
+    #[init(val = 3.0)]
+    /// Distance from the shape at which boids start steering away.
+    margin: f32,
+    #[export]
+    #[init(val = true)]
+    avoidance_enabled: bool,
+    // Last built instance and the global transform it was built for, cleared when the shape changes
+    cached: Option<(Transform3D, ObstacleInstance)>,
+    base: Base<Node3D>,
+}
+
+impl BoidObstacle3D {
+    /// The obstacle as seen by the simulation, `None` while disabled.
+    /// Only rebuilt once the shape or the node's transform changes.
+    pub fn get_obstacle_instance(&mut self) -> Option<ObstacleInstance> {
+        if !self.avoidance_enabled { return None; }
+        let transform = self.base().get_global_transform();
+        let shape = match &self.cached {
+            Some((cached_transform, instance)) if *cached_transform == transform => return Some(instance.clone()),
+            Some((_, instance)) => instance.shape.clone(),
+            None => self.build_shape(),
+        };
+        let instance = ObstacleInstance::new(shape, to_glam_affine_3d(transform), self.margin);
+        self.cached = Some((transform, instance.clone()));
+        Some(instance)
+    }
+
+    fn build_shape(&self) -> ObstacleShape {
+        match self.shape {
+            ObstacleShape3D::Point => ObstacleShape::Point,
+            ObstacleShape3D::Sphere => ObstacleShape::Sphere { radius: self.radius },
+            ObstacleShape3D::Box => ObstacleShape::Box { half_extents: to_glam_vec(self.size).abs() * 0.5 },
+            ObstacleShape3D::Capsule => ObstacleShape::Capsule {
+                radius: self.radius,
+                half_height: (self.height * 0.5 - self.radius).max(0.0),
+            },
+        }
+    }
+}
+
+#[godot_api]
+impl INode3D for BoidObstacle3D {
+    fn enter_tree(&mut self) {
+        get_singleton().bind_mut().register_obstacle_3d(self.get_id())
+    }
+
+    fn exit_tree(&mut self) {
+        get_singleton().bind_mut().unregister_obstacle_3d(self.get_id())
+    }
+}
+
+#[godot_api]
+impl BoidObstacle3D {
+    #[func]
+    pub fn get_id(&self) -> InstanceId {
+        self.base().instance_id()
+    }
+
+    #[func]
+    fn set_shape(&mut self, value: ObstacleShape3D) {
+        self.shape = value;
+        self.cached = None;
+    }
+
+    #[func]
+    fn set_radius(&mut self, value: f32) {
+        self.radius = value;
+        self.cached = None;
+    }
+
+    #[func]
+    fn set_size(&mut self, value: Vector3) {
+        self.size = value;
+        self.cached = None;
+    }
+
+    #[func]
+    fn set_height(&mut self, value: f32) {
+        self.height = value;
+        self.cached = None;
+    }
+
+    #[func]
+    fn set_margin(&mut self, value: f32) {
+        self.margin = value;
+        self.cached = None;
+    }
+}
@@ -0,0 +1,5 @@
+pub mod predator_2d;
+pub mod predator_3d;
+
+pub use predator_2d::*;
+pub use predator_3d::*;
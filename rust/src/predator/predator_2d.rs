@@ -0,0 +1,54 @@
+use boids_core::PredatorInstance;
+use glam::*;
+use godot::prelude::*;
+
+use crate::get_singleton;
+
+#[derive(GodotClass)]
+#[class(init, base=Node2D)]
+/// Threat 2D boids of every flock flee from, add it as a child of anything that should scare them.
+///
+/// Boids within `panic_radius` flee, the closer the harder. How hard is set by `flee` in `BoidProperties`.
+pub struct BoidPredator2D {
+    #[export]
+    #[init(val = 128.0)]
+    /// Distance within which boids flee.
+    panic_radius: f32,
+    #[export]
+    /// Extra speed, relative to `max_speed`, boids may reach while fleeing right next to the predator.
+    /// The boost fades once they got away.
+    speed_boost: f32,
+    #[export]
+    #[init(val = true)]
+    threat_enabled: bool,
+    base: Base<Node2D>,
+}
+
+impl BoidPredator2D {
+    /// The predator as seen by the simulation, `None` while disabled.
+    pub fn get_predator_instance(&self) -> Option<PredatorInstance> {
+        if !self.threat_enabled { return None; }
+        let pos = self.base().get_global_position();
+        let position = vec3(pos.x, pos.y, 0.0);
+        Some(PredatorInstance::new(position, self.panic_radius, self.speed_boost))
+    }
+}
+
+#[godot_api]
+impl INode2D for BoidPredator2D {
+    fn enter_tree(&mut self) {
+        get_singleton().bind_mut().register_predator_2d(self.get_id())
+    }
+
+    fn exit_tree(&mut self) {
+        get_singleton().bind_mut().unregister_predator_2d(self.get_id())
+    }
+}
+
+#[godot_api]
+impl BoidPredator2D {
+    #[func]
+    pub fn get_id(&self) -> InstanceId {
+        self.base().instance_id()
+    }
+}
@@ -0,0 +1,187 @@
+use boids_core::BoidInstance;
+use glam::*;
+use godot::{
+    classes::{multi_mesh::TransformFormat, MultiMesh},
+    prelude::*,
+};
+
+use crate::{flock::{BoidNode, BoidStore}, BoidOrientation};
+
+/// Multimesh instance a swarm boid is drawn as, mirrored like a boid node.
+pub trait SwarmInstance: BoidNode {
+    const TRANSFORM_FORMAT: TransformFormat;
+    /// Floats per instance transform in a multimesh buffer.
+    const TRANSFORM_FLOATS: usize;
+    /// Writes the rows of the instance transform relative to the swarm.
+    /// `unrotate` undoes the swarm's global rotation, so instances keep their global heading.
+    fn write_transform(&self, to_local: &Affine3A, unrotate: Quat, transform: &mut [f32]);
+    /// Custom data handed to shaders, `speed` is relative to `max_speed`.
+    fn custom_data(&self, boid: &BoidInstance, speed: f32) -> [f32; 4];
+}
+
+/// Multimesh instance a swarm boid is drawn as, mirrored like a boid node. Positions and rotations are global.
+#[derive(Clone, Copy, Debug)]
+pub struct SwarmInstance2D {
+    pub position: Vec2,
+    /// Global rotation, `None` draws the instance unrotated relative to the swarm.
+    pub angle: Option<f32>,
+    /// Random value, handed to shaders through custom data.
+    pub seed: f32,
+}
+
+impl BoidNode for SwarmInstance2D {
+    #[inline(always)]
+    fn read_position(&self) -> Vec3 {
+        self.position.extend(0.0)
+    }
+
+    #[inline(always)]
+    fn write_position(&mut self, position: Vec3) {
+        self.position = position.xy();
+        self.angle = None;
+    }
+
+    fn write_oriented(&mut self, boid: &BoidInstance, orientation: &BoidOrientation, delta: f32) {
+        // Instances that weren't oriented before face along the velocity right away
+        let angle = self.angle.or_else(|| orientation.angle_2d(boid)).unwrap_or(0.0);
+        self.position = boid.position.xy();
+        self.angle = Some(orientation.turn_2d(angle, boid, delta));
+    }
+}
+
+impl SwarmInstance for SwarmInstance2D {
+    const TRANSFORM_FORMAT: TransformFormat = TransformFormat::TRANSFORM_2D;
+    const TRANSFORM_FLOATS: usize = 8;
+
+    fn write_transform(&self, to_local: &Affine3A, unrotate: Quat, transform: &mut [f32]) {
+        let x = self.angle.map_or(Vec2::X, |angle| (unrotate * Vec2::from_angle(angle).extend(0.0)).xy());
+        let y = x.perp();
+        let origin = to_local.transform_point3(self.position.extend(0.0));
+        // Rows of the 2x4 transform matrix, the third column is padding
+        transform.copy_from_slice(&[
+            x.x, y.x, 0.0, origin.x,
+            x.y, y.y, 0.0, origin.y,
+        ]);
+    }
+
+    fn custom_data(&self, boid: &BoidInstance, speed: f32) -> [f32; 4] {
+        let dir = boid.velocity.xy().try_normalize().unwrap_or(Vec2::X);
+        [dir.x, dir.y, speed, self.seed]
+    }
+}
+
+/// Multimesh instance a swarm boid is drawn as, mirrored like a boid node. Positions and rotations are global.
+#[derive(Clone, Copy, Debug)]
+pub struct SwarmInstance3D {
+    pub position: Vec3,
+    /// Global rotation, `None` draws the instance unrotated relative to the swarm.
+    pub rotation: Option<Quat>,
+}
+
+impl BoidNode for SwarmInstance3D {
+    #[inline(always)]
+    fn read_position(&self) -> Vec3 {
+        self.position
+    }
+
+    #[inline(always)]
+    fn write_position(&mut self, position: Vec3) {
+        self.position = position;
+        self.rotation = None;
+    }
+
+    fn write_oriented(&mut self, boid: &BoidInstance, orientation: &BoidOrientation, delta: f32) {
+        // Instances that weren't oriented before face along the velocity right away
+        let rotation = self.rotation.or_else(|| orientation.rotation_3d(boid)).unwrap_or(Quat::IDENTITY);
+        self.position = boid.position;
+        self.rotation = Some(orientation.turn_3d(rotation, boid, delta));
+    }
+}
+
+impl SwarmInstance for SwarmInstance3D {
+    const TRANSFORM_FORMAT: TransformFormat = TransformFormat::TRANSFORM_3D;
+    const TRANSFORM_FLOATS: usize = 12;
+
+    fn write_transform(&self, to_local: &Affine3A, unrotate: Quat, transform: &mut [f32]) {
+        let basis = self.rotation.map_or(Mat3::IDENTITY, |rotation| Mat3::from_quat(unrotate * rotation));
+        let (x, y, z) = (basis.x_axis, basis.y_axis, basis.z_axis);
+        let origin = to_local.transform_point3(self.position);
+        // Rows of the 3x4 transform matrix
+        transform.copy_from_slice(&[
+            x.x, y.x, z.x, origin.x,
+            x.y, y.y, z.y, origin.y,
+            x.z, y.z, z.z, origin.z,
+        ]);
+    }
+
+    fn custom_data(&self, boid: &BoidInstance, speed: f32) -> [f32; 4] {
+        let dir = boid.velocity.normalize_or_zero();
+        [dir.x, dir.y, dir.z, speed]
+    }
+}
+
+/// Readies the multimesh of a swarm drawing `I` instances. Returns a new multimesh to draw with if there is none.
+pub fn prepare_multimesh<I: SwarmInstance>(multimesh: Option<Gd<MultiMesh>>, use_colors: bool) -> Option<Gd<MultiMesh>> {
+    // The transform format can only change while empty
+    match multimesh {
+        Some(mut multimesh) if multimesh.get_transform_format() != I::TRANSFORM_FORMAT => {
+            multimesh.set_instance_count(0);
+            multimesh.set_transform_format(I::TRANSFORM_FORMAT);
+            None
+        }
+        Some(_) => None,
+        None => {
+            let mut multimesh = MultiMesh::new_gd();
+            multimesh.set_transform_format(I::TRANSFORM_FORMAT);
+            multimesh.set_use_colors(use_colors);
+            Some(multimesh)
+        }
+    }
+}
+
+impl<I: SwarmInstance> BoidStore<I> {
+    /// Writes the transforms, colors and custom data of every instance into `buffer` and uploads it to `multimesh` in one call.
+    ///
+    /// Instances are drawn relative to the swarm at `to_global`, only its rotation is undone so its scale still applies.
+    /// Colors go from the first to the second of `colors` as boids speed up to `max_speed`.
+    pub fn upload_instances(
+        &self,
+        multimesh: &mut Gd<MultiMesh>,
+        buffer: &mut PackedFloat32Array,
+        to_global: Affine3A,
+        max_speed: f32,
+        colors: (Color, Color),
+    ) {
+        if multimesh.get_instance_count() as usize != self.len() {
+            multimesh.set_instance_count(self.len() as i32);
+        }
+
+        let use_colors = multimesh.is_using_colors();
+        let use_custom_data = multimesh.is_using_custom_data();
+        let stride = I::TRANSFORM_FLOATS + 4 * use_colors as usize + 4 * use_custom_data as usize;
+        buffer.resize(self.len() * stride);
+
+        let to_local = to_global.inverse();
+        let (_, rotation, _) = to_global.to_scale_rotation_translation();
+        let unrotate = rotation.inverse();
+
+        let max_speed = max_speed.max(f32::EPSILON);
+        let (color, fast_color) = colors;
+        let boids = self.boids().iter().zip(self.nodes());
+        for ((boid, swarm_instance), instance) in boids.zip(buffer.as_mut_slice().chunks_exact_mut(stride)) {
+            swarm_instance.write_transform(&to_local, unrotate, &mut instance[..I::TRANSFORM_FLOATS]);
+            let speed = boid.velocity.length() / max_speed;
+            let mut extra = I::TRANSFORM_FLOATS;
+            if use_colors {
+                let c = color.lerp(fast_color, speed.min(1.0) as f64);
+                instance[extra..extra + 4].copy_from_slice(&[c.r, c.g, c.b, c.a]);
+                extra += 4;
+            }
+            if use_custom_data {
+                instance[extra..extra + 4].copy_from_slice(&swarm_instance.custom_data(boid, speed));
+            }
+        }
+
+        multimesh.set_buffer(&*buffer);
+    }
+}
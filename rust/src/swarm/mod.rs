@@ -0,0 +1,7 @@
+pub mod instance;
+pub mod swarm_2d;
+pub mod swarm_3d;
+
+pub use instance::*;
+pub use swarm_2d::*;
+pub use swarm_3d::*;
@@ -0,0 +1,248 @@
+use boids_core::{BoidInstance, BoidParams, BoundsInstance, FlockParams, SpatialIndexKind};
+use glam::*;
+use godot::{
+    classes::{IMultiMeshInstance2D, MultiMeshInstance2D},
+    global::randf_range,
+    prelude::*,
+};
+
+use crate::flock::{BoidStore, Flock};
+use crate::{get_singleton, prepare_multimesh, swap_properties, to_glam_affine_2d, to_glam_affine_2d_in_3d, BoidOrientation, BoidProperties, FlockBounds, FlockProperties, FlockRelation, FlockSpatialIndex, SwarmInstance2D};
+
+#[derive(GodotClass)]
+#[class(init, base=MultiMeshInstance2D)]
+/// A flock whose boids only exist as data in the simulation, drawn as instances of its `MultiMesh`.
+///
+/// Every tick the instance transforms are written in one bulk buffer upload, rotated along each boid's velocity unless
+/// `orient_to_velocity` is off.
+/// Boids are simulated in global space, so they don't follow when the swarm node moves. Scaling the node scales the boids.
+/// If the multimesh uses colors, each boid is colored between `color` and `fast_color` by its speed.
+/// If it uses custom data, it holds the boid's direction, its speed relative to `max_speed` and a random value per boid.
+pub struct BoidSwarm2D {
+    #[export]
+    #[var(get, set = set_properties)]
+    properties: Option<Gd<FlockProperties>>,
+    props: FlockParams,
+    #[export]
+    #[var(get, set = set_boid_properties)]
+    /// Properties shared by every boid of the swarm.
+    boid_properties: Option<Gd<BoidProperties>>,
+    boid_props: BoidParams,
+    #[export]
+    target: Option<Gd<Node2D>>,
+    #[export]
+    /// How boids of this swarm react to boids of other flocks, paths are relative to the swarm.
+    relations: Array<Gd<FlockRelation>>,
+    #[export]
+    /// Spatial structure used to find neighbouring boids of this swarm.
+    spatial_index: FlockSpatialIndex,
+    #[export]
+    /// Volume the boids are kept in. Unlike the boids, it moves along with the swarm node.
+    bounds: Option<Gd<FlockBounds>>,
+    #[export]
+    /// Seed of the noise the boids wander along. Boids spawned in the same order wander the same way.
+    wander_seed: u32,
+    #[export]
+    #[var(get, set = set_boid_count)]
+    #[init(val = 1000)]
+    /// Amount of boids in the swarm. New boids spawn at random inside `spawn_extents`.
+    boid_count: i64,
+    #[export]
+    #[init(val = Vector2::new(256.0, 256.0))]
+    /// Half size of the rect, centered on the swarm, new boids spawn in.
+    spawn_extents: Vector2,
+    #[export]
+    #[init(val = Color::WHITE)]
+    /// Color of standing boids.
+    color: Color,
+    #[export]
+    #[init(val = Color::WHITE)]
+    /// Color of boids moving at `max_speed`.
+    fast_color: Color,
+    #[export]
+    #[var(get, set = set_orient_to_velocity)]
+    #[init(val = true)]
+    /// Turns boids to face along their velocity, +X being forward like for `Node2D.rotation`.
+    orient_to_velocity: bool,
+    #[export]
+    #[var(get, set = set_orientation_smoothing)]
+    /// Time constant of turning towards the velocity, in seconds. 0 turns instantly.
+    orientation_smoothing: f32,
+    #[export]
+    #[init(val = true)]
+    boid_processing_enabled: bool,
+    boids: BoidStore<SwarmInstance2D>,
+    buffer: PackedFloat32Array,
+    base: Base<MultiMeshInstance2D>,
+}
+
+impl BoidSwarm2D {
+    // Spawns or removes boids until the swarm holds `boid_count` of them
+    fn resize_boids(&mut self) {
+        let count = self.boid_count.max(0) as usize;
+        self.boids.truncate(count);
+        let orientation = self.get_orientation();
+        let extents = vec2(self.spawn_extents.x, self.spawn_extents.y);
+        let speed = self.boid_props.max_speed;
+        let to_global = to_glam_affine_2d(self.base().get_global_transform());
+        while self.boids.len() < count {
+            let random = || vec2(randf_range(-1.0, 1.0) as f32, randf_range(-1.0, 1.0) as f32);
+            let position = to_global.transform_point2(random() * extents);
+            let velocity = random().normalize_or_zero() * speed;
+            let boid = BoidInstance::new(position.extend(0.0), velocity.extend(0.0), self.boid_props, 0);
+            let angle = orientation.and_then(|orientation| orientation.angle_2d(&boid));
+            let instance = SwarmInstance2D { position, angle, seed: randf_range(0.0, 1.0) as f32 };
+            self.boids.push(instance, boid.velocity, self.boid_props, orientation);
+        }
+    }
+
+    // How boids are turned, `None` unless oriented along their velocity
+    fn get_orientation(&self) -> Option<BoidOrientation> {
+        self.orient_to_velocity.then(|| BoidOrientation {
+            smoothing: self.orientation_smoothing,
+            ..Default::default()
+        })
+    }
+
+    // Uploads the transforms, colors and custom data of every instance to the multimesh in one call
+    fn write_instances(&mut self) {
+        let Some(mut multimesh) = self.base().get_multimesh() else { return; };
+        let to_global = to_glam_affine_2d_in_3d(self.base().get_global_transform());
+        let colors = (self.color, self.fast_color);
+        self.boids.upload_instances(&mut multimesh, &mut self.buffer, to_global, self.boid_props.max_speed, colors);
+    }
+}
+
+#[godot_api]
+impl IMultiMeshInstance2D for BoidSwarm2D {
+    fn enter_tree(&mut self) {
+        get_singleton().bind_mut().register_swarm_2d(self.get_id())
+    }
+
+    fn ready(&mut self) {
+        // Instances are written as 2D transforms
+        let multimesh = self.base().get_multimesh();
+        if let Some(multimesh) = prepare_multimesh::<SwarmInstance2D>(multimesh, true) {
+            self.base_mut().set_multimesh(&multimesh);
+        }
+
+        self.resize_boids();
+        self.write_instances();
+    }
+
+    fn exit_tree(&mut self) {
+        get_singleton().bind_mut().unregister_swarm_2d(self.get_id())
+    }
+}
+
+#[godot_api]
+impl BoidSwarm2D {
+    #[func]
+    pub fn get_id(&self) -> InstanceId {
+        self.base().instance_id()
+    }
+
+    #[func]
+    fn set_properties(&mut self, properties: Option<Gd<FlockProperties>>) {
+        let on_changed = self.base().callable("on_properties_changed");
+        swap_properties(&mut self.properties, properties, &on_changed);
+        self.on_properties_changed();
+    }
+
+    #[func]
+    fn on_properties_changed(&mut self) {
+        self.props = self.properties.as_ref().map(|props| (&*props.bind()).into()).unwrap_or_default();
+    }
+
+    #[func]
+    fn set_boid_properties(&mut self, properties: Option<Gd<BoidProperties>>) {
+        let on_changed = self.base().callable("on_boid_properties_changed");
+        swap_properties(&mut self.boid_properties, properties, &on_changed);
+        self.on_boid_properties_changed();
+    }
+
+    #[func]
+    fn on_boid_properties_changed(&mut self) {
+        self.boid_props = self.boid_properties.as_ref().map(|props| (&*props.bind()).into()).unwrap_or_default();
+        for boid in self.boids.boids_mut() {
+            boid.properties = self.boid_props;
+        }
+    }
+
+    #[func]
+    fn set_boid_count(&mut self, count: i64) {
+        self.boid_count = count.max(0);
+        // Before `ready` the properties of new boids may not be set yet, `ready` spawns them
+        if self.base().is_node_ready() {
+            self.resize_boids();
+            self.write_instances();
+        }
+    }
+
+    #[func]
+    fn set_orient_to_velocity(&mut self, enabled: bool) {
+        self.orient_to_velocity = enabled;
+        self.boids.set_orientations(self.get_orientation());
+    }
+
+    #[func]
+    fn set_orientation_smoothing(&mut self, smoothing: f32) {
+        self.orientation_smoothing = smoothing.max(0.0);
+        self.boids.set_orientations(self.get_orientation());
+    }
+}
+
+impl Flock for BoidSwarm2D {
+    fn get_flock_properties(&self) -> &FlockParams {
+        &self.props
+    }
+
+    fn get_target_position(&self) -> Option<Vec3> {
+        self.target.as_ref().map(|t| {
+            let pos = t.get_global_position();
+            vec3(pos.x, pos.y, 0.0)
+        })
+    }
+
+    fn get_boids(&self) -> &[BoidInstance] {
+        self.boids.boids()
+    }
+
+    // Swarm boids have no nodes that could be moved from outside
+    fn pull_boid_teleports(&mut self) {}
+
+    fn step_boids(&mut self, processed: &[BoidInstance], delta: f32) {
+        let bounds = self.get_flock_bounds();
+        self.boids.step(processed, delta, bounds.as_ref());
+        self.write_instances();
+    }
+
+    fn get_flock_node(&self) -> Gd<Node> {
+        self.to_gd().upcast()
+    }
+
+    fn get_relations(&self) -> &Array<Gd<FlockRelation>> {
+        &self.relations
+    }
+
+    fn get_spatial_index_kind(&self) -> SpatialIndexKind {
+        self.spatial_index.into()
+    }
+
+    fn get_flock_bounds(&self) -> Option<BoundsInstance> {
+        let transform = to_glam_affine_2d_in_3d(self.base().get_global_transform());
+        self.bounds.as_ref().map(|bounds| bounds.bind().to_bounds_instance(transform, true))
+    }
+
+    fn get_wander_seed(&self) -> u32 {
+        self.wander_seed
+    }
+
+    fn get_wander_time(&self) -> f64 {
+        self.boids.time()
+    }
+
+    fn is_boid_processing(&self) -> bool {
+        self.boid_processing_enabled
+    }
+}
@@ -0,0 +1,258 @@
+use boids_core::{BoidInstance, BoidParams, BoundsInstance, FlockParams, SpatialIndexKind};
+use glam::*;
+use godot::{
+    classes::{IMultiMeshInstance3D, MultiMeshInstance3D},
+    global::randf_range,
+    prelude::*,
+};
+
+use crate::flock::{BoidStore, Flock};
+use crate::{get_singleton, prepare_multimesh, swap_properties, to_glam_affine_3d, to_glam_vec, BoidOrientation, BoidProperties, FlockBounds, FlockProperties, FlockRelation, FlockSpatialIndex, SwarmInstance3D};
+
+#[derive(GodotClass)]
+#[class(init, base=MultiMeshInstance3D)]
+/// A flock whose boids only exist as data in the simulation, drawn as instances of its `MultiMesh`.
+///
+/// Every tick the instance transforms are written in one bulk buffer upload, facing along each boid's velocity unless
+/// `orient_to_velocity` is off.
+/// Boids are simulated in global space, so they don't follow when the swarm node moves. Scaling the node scales the boids.
+/// If the multimesh uses custom data, it holds the boid's direction and its speed relative to `max_speed`.
+pub struct BoidSwarm3D {
+    #[export]
+    #[var(get, set = set_properties)]
+    properties: Option<Gd<FlockProperties>>,
+    props: FlockParams,
+    #[export]
+    #[var(get, set = set_boid_properties)]
+    /// Properties shared by every boid of the swarm.
+    boid_properties: Option<Gd<BoidProperties>>,
+    boid_props: BoidParams,
+    #[export]
+    target: Option<Gd<Node3D>>,
+    #[export]
+    /// How boids of this swarm react to boids of other flocks, paths are relative to the swarm.
+    relations: Array<Gd<FlockRelation>>,
+    #[export]
+    /// Spatial structure used to find neighbouring boids of this swarm.
+    spatial_index: FlockSpatialIndex,
+    #[export]
+    /// Volume the boids are kept in. Unlike the boids, it moves along with the swarm node.
+    bounds: Option<Gd<FlockBounds>>,
+    #[export]
+    /// Seed of the noise the boids wander along. Boids spawned in the same order wander the same way.
+    wander_seed: u32,
+    #[export]
+    #[var(get, set = set_boid_count)]
+    #[init(val = 1000)]
+    /// Amount of boids in the swarm. New boids spawn at random inside `spawn_extents`.
+    boid_count: i64,
+    #[export]
+    #[init(val = Vector3::new(20.0, 20.0, 20.0))]
+    /// Half size of the box, centered on the swarm, new boids spawn in.
+    spawn_extents: Vector3,
+    #[export]
+    #[var(get, set = set_orient_to_velocity)]
+    #[init(val = true)]
+    /// Turns boids to face along their velocity, -Z being forward like for `look_at`.
+    orient_to_velocity: bool,
+    #[export]
+    #[var(get, set = set_up_vector)]
+    #[init(val = Vector3::UP)]
+    /// Direction the top of boids is kept towards while oriented.
+    up_vector: Vector3,
+    #[export]
+    #[var(get, set = set_orientation_smoothing)]
+    /// Time constant of turning towards the velocity, in seconds. 0 turns instantly.
+    orientation_smoothing: f32,
+    #[export(range = (0.0, 90.0, radians_as_degrees))]
+    #[var(get, set = set_max_bank_angle)]
+    /// Roll into turns at full sideways steering force.
+    max_bank_angle: f32,
+    #[export]
+    #[init(val = true)]
+    boid_processing_enabled: bool,
+    boids: BoidStore<SwarmInstance3D>,
+    buffer: PackedFloat32Array,
+    base: Base<MultiMeshInstance3D>,
+}
+
+impl BoidSwarm3D {
+    // Spawns or removes boids until the swarm holds `boid_count` of them
+    fn resize_boids(&mut self) {
+        let count = self.boid_count.max(0) as usize;
+        self.boids.truncate(count);
+        let orientation = self.get_orientation();
+        let extents = to_glam_vec(self.spawn_extents);
+        let speed = self.boid_props.max_speed;
+        let to_global = to_glam_affine_3d(self.base().get_global_transform());
+        while self.boids.len() < count {
+            let random = || vec3(randf_range(-1.0, 1.0) as f32, randf_range(-1.0, 1.0) as f32, randf_range(-1.0, 1.0) as f32);
+            let position = to_global.transform_point3(random() * extents);
+            let velocity = random().normalize_or_zero() * speed;
+            let boid = BoidInstance::new(position, velocity, self.boid_props, 0);
+            let rotation = orientation.and_then(|orientation| orientation.rotation_3d(&boid));
+            self.boids.push(SwarmInstance3D { position, rotation }, velocity, self.boid_props, orientation);
+        }
+    }
+
+    // How boids are turned, `None` unless oriented along their velocity
+    fn get_orientation(&self) -> Option<BoidOrientation> {
+        self.orient_to_velocity.then(|| BoidOrientation {
+            up: to_glam_vec(self.up_vector),
+            smoothing: self.orientation_smoothing,
+            max_bank: self.max_bank_angle,
+        })
+    }
+
+    // Uploads the transforms and custom data of every instance to the multimesh in one call
+    fn write_instances(&mut self) {
+        let Some(mut multimesh) = self.base().get_multimesh() else { return; };
+        let to_global = to_glam_affine_3d(self.base().get_global_transform());
+        // 3D swarms aren't tinted, instance colors stay white
+        let colors = (Color::WHITE, Color::WHITE);
+        self.boids.upload_instances(&mut multimesh, &mut self.buffer, to_global, self.boid_props.max_speed, colors);
+    }
+}
+
+#[godot_api]
+impl IMultiMeshInstance3D for BoidSwarm3D {
+    fn enter_tree(&mut self) {
+        get_singleton().bind_mut().register_swarm_3d(self.get_id())
+    }
+
+    fn ready(&mut self) {
+        // Instances are written as 3D transforms
+        let multimesh = self.base().get_multimesh();
+        if let Some(multimesh) = prepare_multimesh::<SwarmInstance3D>(multimesh, false) {
+            self.base_mut().set_multimesh(&multimesh);
+        }
+
+        self.resize_boids();
+        self.write_instances();
+    }
+
+    fn exit_tree(&mut self) {
+        get_singleton().bind_mut().unregister_swarm_3d(self.get_id())
+    }
+}
+
+#[godot_api]
+impl BoidSwarm3D {
+    #[func]
+    pub fn get_id(&self) -> InstanceId {
+        self.base().instance_id()
+    }
+
+    #[func]
+    fn set_properties(&mut self, properties: Option<Gd<FlockProperties>>) {
+        let on_changed = self.base().callable("on_properties_changed");
+        swap_properties(&mut self.properties, properties, &on_changed);
+        self.on_properties_changed();
+    }
+
+    #[func]
+    fn on_properties_changed(&mut self) {
+        self.props = self.properties.as_ref().map(|props| (&*props.bind()).into()).unwrap_or_default();
+    }
+
+    #[func]
+    fn set_boid_properties(&mut self, properties: Option<Gd<BoidProperties>>) {
+        let on_changed = self.base().callable("on_boid_properties_changed");
+        swap_properties(&mut self.boid_properties, properties, &on_changed);
+        self.on_boid_properties_changed();
+    }
+
+    #[func]
+    fn on_boid_properties_changed(&mut self) {
+        self.boid_props = self.boid_properties.as_ref().map(|props| (&*props.bind()).into()).unwrap_or_default();
+        for boid in self.boids.boids_mut() {
+            boid.properties = self.boid_props;
+        }
+    }
+
+    #[func]
+    fn set_boid_count(&mut self, count: i64) {
+        self.boid_count = count.max(0);
+        // Before `ready` the properties of new boids may not be set yet, `ready` spawns them
+        if self.base().is_node_ready() {
+            self.resize_boids();
+            self.write_instances();
+        }
+    }
+
+    #[func]
+    fn set_orient_to_velocity(&mut self, enabled: bool) {
+        self.orient_to_velocity = enabled;
+        self.boids.set_orientations(self.get_orientation());
+    }
+
+    #[func]
+    fn set_up_vector(&mut self, up: Vector3) {
+        self.up_vector = up;
+        self.boids.set_orientations(self.get_orientation());
+    }
+
+    #[func]
+    fn set_orientation_smoothing(&mut self, smoothing: f32) {
+        self.orientation_smoothing = smoothing.max(0.0);
+        self.boids.set_orientations(self.get_orientation());
+    }
+
+    #[func]
+    fn set_max_bank_angle(&mut self, angle: f32) {
+        self.max_bank_angle = angle.max(0.0);
+        self.boids.set_orientations(self.get_orientation());
+    }
+}
+
+impl Flock for BoidSwarm3D {
+    fn get_flock_properties(&self) -> &FlockParams {
+        &self.props
+    }
+
+    fn get_target_position(&self) -> Option<Vec3> {
+        self.target.as_ref().map(|t| to_glam_vec(t.get_global_position()))
+    }
+
+    fn get_boids(&self) -> &[BoidInstance] {
+        self.boids.boids()
+    }
+
+    // Swarm boids have no nodes that could be moved from outside
+    fn pull_boid_teleports(&mut self) {}
+
+    fn step_boids(&mut self, processed: &[BoidInstance], delta: f32) {
+        let bounds = self.get_flock_bounds();
+        self.boids.step(processed, delta, bounds.as_ref());
+        self.write_instances();
+    }
+
+    fn get_flock_node(&self) -> Gd<Node> {
+        self.to_gd().upcast()
+    }
+
+    fn get_relations(&self) -> &Array<Gd<FlockRelation>> {
+        &self.relations
+    }
+
+    fn get_spatial_index_kind(&self) -> SpatialIndexKind {
+        self.spatial_index.into()
+    }
+
+    fn get_flock_bounds(&self) -> Option<BoundsInstance> {
+        let transform = to_glam_affine_3d(self.base().get_global_transform());
+        self.bounds.as_ref().map(|bounds| bounds.bind().to_bounds_instance(transform, false))
+    }
+
+    fn get_wander_seed(&self) -> u32 {
+        self.wander_seed
+    }
+
+    fn get_wander_time(&self) -> f64 {
+        self.boids.time()
+    }
+
+    fn is_boid_processing(&self) -> bool {
+        self.boid_processing_enabled
+    }
+}